@@ -0,0 +1,52 @@
+// Compares the circle stamping of draw_line with the anti-aliased BrushStroke on a 4K capture.
+// Run with: cargo run --release --example stroke_bench
+
+use snap_rust::tool_utils::{draw_line, BrushStroke};
+
+use image::DynamicImage;
+use std::time::{Duration, Instant};
+
+const WIDTH: u32 = 3840;
+const HEIGHT: u32 = 2160;
+
+// A wavy drag across the screen, with points as far apart as fast mouse events
+fn get_drag_points() -> Vec<(f32, f32)> {
+    return (0..1000).map(|i| {
+        let t = i as f32 / 1000.;
+        (100. + t * 3600., 1080. + (t * 40.).sin() * 600.)
+    }).collect();
+}
+
+// Best of a few runs, the first ones also pay for the allocator warming up
+fn time<F: FnMut(&mut DynamicImage)>(mut f: F) -> Duration {
+    return (0..5).map(|_| {
+        let mut img = DynamicImage::new_rgba8(WIDTH, HEIGHT);
+        let start = Instant::now();
+        f(&mut img);
+        start.elapsed()
+    }).min().unwrap();
+}
+
+fn main() {
+    let points = get_drag_points();
+    let color = [230, 76, 60, 255];
+
+    for pen_size in [1, 5, 15] {
+        let width = (pen_size * 2 + 1) as f32;
+
+        let circles = time(|img| {
+            for segment in points.windows(2) {
+                draw_line(img, segment[0], segment[1], pen_size, color);
+            }
+        });
+
+        let brush = time(|img| {
+            let mut stroke = BrushStroke::new(img, points[0], color, width);
+            for point in &points[1..] {
+                stroke.add_point(img, *point);
+            }
+        });
+
+        println!("width {:>2}px: circles {:>8.1?}, anti-aliased {:>8.1?}", width, circles, brush);
+    }
+}
@@ -0,0 +1,189 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use imageproc::filter::separable_filter_equal;
+
+// Filters run over the whole capture, below the annotations. The default leaves the image as it is.
+#[derive(PartialEq, Clone, Copy)]
+pub struct Adjustments {
+    // From -100 to 100
+    pub brightness: f32,
+    pub contrast: f32,
+    pub saturation: f32,
+    // Above 1 brightens the midtones, below 1 darkens them
+    pub gamma: f32,
+    pub grayscale: bool,
+    pub invert: bool,
+    // Unsharp mask: how much of the detail is added back, the blur radius in pixels and the
+    // smallest difference with the blurred image that gets sharpened
+    pub sharpen_amount: f32,
+    pub sharpen_radius: f32,
+    pub sharpen_threshold: u8,
+}
+
+impl Default for Adjustments {
+    fn default() -> Self {
+        Adjustments {
+            brightness: 0.,
+            contrast: 0.,
+            saturation: 0.,
+            gamma: 1.,
+            grayscale: false,
+            invert: false,
+            sharpen_amount: 0.,
+            sharpen_radius: 1.,
+            sharpen_threshold: 0,
+        }
+    }
+}
+
+impl Adjustments {
+    pub fn is_identity(&self) -> bool {
+        return *self == Adjustments::default();
+    }
+}
+
+// imageproc's gaussian_blur_f32 cuts its kernel at 2 sigma without normalizing it, which darkens the
+// image and would make sharpening brighten flat areas. This kernel goes to 3 sigma and sums to 1.
+fn gaussian_blur(img: &RgbaImage, sigma: f32) -> RgbaImage {
+    let radius = (sigma * 3.).ceil() as i32;
+    let mut kernel: Vec<f32> = (-radius..=radius).map(|x| (-(x * x) as f32 / (2. * sigma * sigma)).exp()).collect();
+    let sum: f32 = kernel.iter().sum();
+    for value in kernel.iter_mut() {
+        *value /= sum;
+    }
+    return separable_filter_equal(img, &kernel);
+}
+
+// Applies the adjustments to a copy of the image. scale is the size of the image compared to the
+// capture, to preview the sharpening on a smaller copy.
+pub fn adjust_image(image: &DynamicImage, adjustments: &Adjustments, scale: f32) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+
+    if adjustments.sharpen_amount > 0. {
+        let blurred = gaussian_blur(&rgba, (adjustments.sharpen_radius * scale).max(0.1));
+        let threshold = adjustments.sharpen_threshold as f32;
+        for (pixel, blurred_pixel) in rgba.pixels_mut().zip(blurred.pixels()) {
+            for channel in 0..3 {
+                let value = pixel[channel] as f32;
+                let difference = value - blurred_pixel[channel] as f32;
+                if difference.abs() >= threshold {
+                    pixel[channel] = (value + difference * adjustments.sharpen_amount).round().clamp(0., 255.) as u8;
+                }
+            }
+        }
+    }
+
+    // Brightness, contrast and gamma only depend on the channel value, so they are computed once
+    // for each of the 256 values
+    let contrast = (1. + adjustments.contrast / 100.).powi(2);
+    let table: Vec<u8> = (0..256).map(|value| {
+        let mut value = value as f32 / 255. + adjustments.brightness / 100.;
+        value = (value - 0.5) * contrast + 0.5;
+        value = value.clamp(0., 1.).powf(1. / adjustments.gamma);
+        (value * 255.).round() as u8
+    }).collect();
+
+    let saturation = if adjustments.grayscale { 0. } else { 1. + adjustments.saturation / 100. };
+    for pixel in rgba.pixels_mut() {
+        let Rgba([red, green, blue, alpha]) = *pixel;
+        let mut color = [table[red as usize] as f32, table[green as usize] as f32, table[blue as usize] as f32];
+
+        if saturation != 1. {
+            let luma = 0.299 * color[0] + 0.587 * color[1] + 0.114 * color[2];
+            for value in color.iter_mut() {
+                *value = (luma + (*value - luma) * saturation).clamp(0., 255.);
+            }
+        }
+        if adjustments.invert {
+            for value in color.iter_mut() {
+                *value = 255. - *value;
+            }
+        }
+
+        *pixel = Rgba([color[0].round() as u8, color[1].round() as u8, color[2].round() as u8, alpha]);
+    }
+
+    return DynamicImage::ImageRgba8(rgba);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    fn get_pixel_after(pixel: [u8; 4], adjustments: &Adjustments) -> [u8; 4] {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba(pixel)));
+        return adjust_image(&img, adjustments, 1.).to_rgba8().get_pixel(0, 0).0;
+    }
+
+    // Left half at left, right half at right
+    fn get_step_image(left: u8, right: u8) -> DynamicImage {
+        return DynamicImage::ImageRgba8(RgbaImage::from_fn(20, 10, |x, _| {
+            let value = if x < 10 { left } else { right };
+            Rgba([value, value, value, 255])
+        }));
+    }
+
+    #[test]
+    fn keeps_the_image_by_default() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(16, 16, |x, y| Rgba([(x * 16) as u8, (y * 16) as u8, (x * y) as u8, 255 - x as u8])));
+        let adjustments = Adjustments::default();
+
+        assert!(adjustments.is_identity());
+        assert_eq!(adjust_image(&img, &adjustments, 1.).to_rgba8(), img.to_rgba8());
+    }
+
+    #[test]
+    fn inverts_and_removes_the_colors() {
+        let invert = Adjustments { invert: true, ..Adjustments::default() };
+        assert_eq!(get_pixel_after([10, 20, 30, 200], &invert), [245, 235, 225, 200]);
+
+        let grayscale = Adjustments { grayscale: true, ..Adjustments::default() };
+        assert_eq!(get_pixel_after([255, 0, 0, 255], &grayscale), [76, 76, 76, 255]);
+        assert_eq!(get_pixel_after([0, 0, 255, 100], &grayscale), [29, 29, 29, 100]);
+        // Same as no saturation at all
+        let desaturated = Adjustments { saturation: -100., ..Adjustments::default() };
+        assert_eq!(get_pixel_after([255, 0, 0, 255], &desaturated), [76, 76, 76, 255]);
+    }
+
+    #[test]
+    fn brightens_and_darkens() {
+        let midtone = [128, 128, 128, 255];
+        let brighter = Adjustments { brightness: 20., ..Adjustments::default() };
+        let darker = Adjustments { brightness: -20., ..Adjustments::default() };
+        assert!(get_pixel_after(midtone, &brighter)[0] > 128);
+        assert!(get_pixel_after(midtone, &darker)[0] < 128);
+
+        // Gamma moves the midtones and keeps black and white
+        let gamma_up = Adjustments { gamma: 2., ..Adjustments::default() };
+        let gamma_down = Adjustments { gamma: 0.5, ..Adjustments::default() };
+        assert!(get_pixel_after(midtone, &gamma_up)[0] > 128);
+        assert!(get_pixel_after(midtone, &gamma_down)[0] < 128);
+        for adjustments in [gamma_up, gamma_down] {
+            assert_eq!(get_pixel_after([0, 255, 0, 255], &adjustments), [0, 255, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn sharpens_only_above_the_threshold() {
+        let sharpen = Adjustments { sharpen_amount: 1., sharpen_radius: 1., sharpen_threshold: 0, ..Adjustments::default() };
+        let with_threshold = Adjustments { sharpen_threshold: 10, ..sharpen };
+
+        // The dark side of an edge gets darker and the light side lighter
+        let edge = adjust_image(&get_step_image(100, 200), &sharpen, 1.).to_rgba8();
+        assert!(edge.get_pixel(9, 5)[0] < 100);
+        assert!(edge.get_pixel(10, 5)[0] > 200);
+        let edge_with_threshold = adjust_image(&get_step_image(100, 200), &with_threshold, 1.).to_rgba8();
+        assert_eq!(edge_with_threshold.get_pixel(9, 5), edge.get_pixel(9, 5));
+        assert_eq!(edge_with_threshold.get_pixel(10, 5), edge.get_pixel(10, 5));
+        // Flat areas stay as they are
+        assert_eq!(edge.get_pixel(0, 5)[0], 100);
+        assert_eq!(edge.get_pixel(19, 5)[0], 200);
+
+        // Small differences are left alone with a threshold
+        let small_step = get_step_image(100, 104);
+        assert_ne!(adjust_image(&small_step, &sharpen, 1.).to_rgba8(), small_step.to_rgba8());
+        assert_eq!(adjust_image(&small_step, &with_threshold, 1.).to_rgba8(), small_step.to_rgba8());
+    }
+}
@@ -0,0 +1,550 @@
+use crate::tool_utils::{draw_rectangle, draw_ellipse, draw_arrow, draw_text, draw_step_marker, distance_to_segment, get_arrow_geometry, get_ellipse_points, get_rectangle_points, get_step_marker_style, get_stroke_image, get_text_size, restore_segment, rotate_point, BrushStroke, ArrowHeads, ArrowStyle, Transform};
+
+use egui::epaint::{Mesh, Vertex};
+use egui::{Align2, Color32, ColorImage, FontFamily, FontId, Painter, Pos2, Rect, Shape, Stroke, TextureId, TextureOptions, Vec2};
+use image::DynamicImage;
+use rusttype::Font;
+
+// Something drawn on top of the capture. Positions and sizes are in image pixels, angles in radians.
+#[derive(Clone)]
+pub enum Annotation {
+    // Pen and highlighter strokes, the color alpha is below 255 for the highlighter
+    Stroke {
+        points: Vec<(f32, f32)>,
+        color: [u8; 4],
+        width: f32,
+    },
+    // Brings back the capture under the brush, hiding what was drawn before it
+    Erase {
+        points: Vec<(f32, f32)>,
+        width: f32,
+    },
+    Rectangle {
+        start: (f32, f32),
+        end: (f32, f32),
+        color: [u8; 4],
+        size: i32,
+        filled: bool,
+        // Turn around the center of the rectangle between start and end
+        angle: f32,
+    },
+    Ellipse {
+        start: (f32, f32),
+        end: (f32, f32),
+        color: [u8; 4],
+        size: i32,
+        filled: bool,
+        // Turn around the center of the rectangle between start and end
+        angle: f32,
+    },
+    Arrow {
+        start: (f32, f32),
+        end: (f32, f32),
+        color: [u8; 4],
+        size: i32,
+        heads: ArrowHeads,
+        style: ArrowStyle,
+    },
+    // The fonts are used when flattening, the family shows the same font on screen
+    Text {
+        pos: (f32, f32),
+        text: String,
+        fonts: Vec<Font<'static>>,
+        family: FontFamily,
+        size: f32,
+        color: [u8; 4],
+        background: Option<[u8; 4]>,
+    },
+    Step {
+        center: (f32, f32),
+        radius: f32,
+        number: u32,
+        fonts: Vec<Font<'static>>,
+        family: FontFamily,
+        color: [u8; 4],
+    },
+}
+
+fn to_color32(color: [u8; 4]) -> Color32 {
+    return Color32::from_rgba_unmultiplied(color[0], color[1], color[2], color[3]);
+}
+
+impl Annotation {
+    // Draws the annotation into the image, background is the capture the eraser restores from
+    pub fn rasterize(&self, img: &mut DynamicImage, background: &DynamicImage) {
+        match self {
+            Annotation::Stroke { points, color, width } => {
+                if let Some(start) = points.first() {
+                    let mut stroke = BrushStroke::new(img, *start, *color, *width);
+                    for point in &points[1..] {
+                        stroke.add_point(img, *point);
+                    }
+                }
+            }
+            Annotation::Erase { points, width } => {
+                if let Some(start) = points.first() {
+                    restore_segment(img, background, *start, *start, *width);
+                }
+                for segment in points.windows(2) {
+                    restore_segment(img, background, segment[0], segment[1], *width);
+                }
+            }
+            Annotation::Rectangle { start, end, color, size, filled, angle } => {
+                draw_rectangle(img, *start, *end, *size, *color, *filled, *angle);
+            }
+            Annotation::Ellipse { start, end, color, size, filled, angle } => {
+                draw_ellipse(img, *start, *end, *size, *color, *filled, *angle);
+            }
+            Annotation::Arrow { start, end, color, size, heads, style } => {
+                draw_arrow(img, *start, *end, *size, *color, *heads, *style);
+            }
+            Annotation::Text { pos, text, fonts, size, color, background, .. } => {
+                draw_text(img, *pos, text, fonts, *size, *color, *background);
+            }
+            Annotation::Step { center, radius, number, fonts, color, .. } => {
+                draw_step_marker(img, *center, *radius, *number, fonts, *color);
+            }
+        }
+    }
+
+    // Paints the annotation with egui over the capture shown in image_rect. texture_id is the
+    // texture of the capture, the eraser paints it back.
+    pub fn paint(&self, painter: &Painter, image_rect: Rect, image_size: [usize; 2], texture_id: TextureId) {
+        let scale = image_rect.width() / image_size[0] as f32;
+        let to_screen = |point: (f32, f32)| image_rect.min + Vec2::new(point.0, point.1) * scale;
+
+        match self {
+            Annotation::Stroke { points, color, width } => {
+                // Overlapping shapes would show darker spots where a translucent stroke crosses
+                // itself, so it is rasterized like on export and shown as a texture
+                if color[3] < 255 {
+                    if let Some((offset, image)) = get_stroke_image(points, *color, *width) {
+                        let size = [image.width() as usize, image.height() as usize];
+                        let texture_options = if scale * painter.ctx().pixels_per_point() > 1. { TextureOptions::NEAREST } else { TextureOptions::LINEAR };
+                        let texture_handle = painter.ctx().load_texture("stroke", ColorImage::from_rgba_unmultiplied(size, image.as_raw()), texture_options);
+                        let rect = Rect::from_min_size(to_screen((offset.0 as f32, offset.1 as f32)), Vec2::new(size[0] as f32, size[1] as f32) * scale);
+                        painter.image(texture_handle.id(), rect, Rect::from_min_max(Pos2::ZERO, Pos2::new(1., 1.)), Color32::WHITE);
+                    }
+                }
+                else {
+                    let screen_points: Vec<Pos2> = points.iter().map(|point| to_screen(*point)).collect();
+                    let color = to_color32(*color);
+                    for point in screen_points.iter() {
+                        painter.circle_filled(*point, width * scale / 2., color);
+                    }
+                    if screen_points.len() > 1 {
+                        painter.add(Shape::line(screen_points, Stroke::new(width * scale, color)));
+                    }
+                }
+            }
+            Annotation::Erase { points, width } => {
+                let uv = |point: (f32, f32)| Pos2::new(point.0 / image_size[0] as f32, point.1 / image_size[1] as f32);
+                let radius = width / 2.;
+                let mut mesh = Mesh::with_texture(texture_id);
+                let add_vertex = |mesh: &mut Mesh, point: (f32, f32)| {
+                    mesh.vertices.push(Vertex { pos: to_screen(point), uv: uv(point), color: Color32::WHITE });
+                    return mesh.vertices.len() as u32 - 1;
+                };
+
+                for point in points.iter() {
+                    let center = add_vertex(&mut mesh, *point);
+                    let steps = 16;
+                    let first = mesh.vertices.len() as u32;
+                    for i in 0..steps {
+                        let angle = i as f32 / steps as f32 * std::f32::consts::TAU;
+                        add_vertex(&mut mesh, (point.0 + radius * angle.cos(), point.1 + radius * angle.sin()));
+                    }
+                    for i in 0..steps {
+                        mesh.add_triangle(center, first + i, first + (i + 1) % steps);
+                    }
+                }
+                for segment in points.windows(2) {
+                    let (start, end) = (segment[0], segment[1]);
+                    let length = ((end.0 - start.0).powi(2) + (end.1 - start.1).powi(2)).sqrt();
+                    if length == 0. {
+                        continue;
+                    }
+                    let normal = (-(end.1 - start.1) / length * radius, (end.0 - start.0) / length * radius);
+                    let a = add_vertex(&mut mesh, (start.0 + normal.0, start.1 + normal.1));
+                    let b = add_vertex(&mut mesh, (start.0 - normal.0, start.1 - normal.1));
+                    let c = add_vertex(&mut mesh, (end.0 + normal.0, end.1 + normal.1));
+                    let d = add_vertex(&mut mesh, (end.0 - normal.0, end.1 - normal.1));
+                    mesh.add_triangle(a, b, c);
+                    mesh.add_triangle(b, c, d);
+                }
+                painter.add(Shape::mesh(mesh));
+            }
+            Annotation::Rectangle { start, end, color, size, filled, angle } => {
+                if *angle == 0. {
+                    let rect = Rect::from_two_pos(to_screen(*start), to_screen(*end));
+                    if *filled {
+                        painter.rect_filled(rect, 0., to_color32(*color));
+                    }
+                    else {
+                        painter.rect_stroke(rect, 0., Stroke::new((size * 2 + 1) as f32 * scale, to_color32(*color)));
+                    }
+                }
+                else {
+                    let mut points: Vec<Pos2> = get_rectangle_points(*start, *end, *angle).into_iter().map(to_screen).collect();
+                    points.pop();
+                    if *filled {
+                        painter.add(Shape::convex_polygon(points, to_color32(*color), Stroke::NONE));
+                    }
+                    else {
+                        painter.add(Shape::closed_line(points, Stroke::new((size * 2 + 1) as f32 * scale, to_color32(*color))));
+                    }
+                }
+            }
+            Annotation::Ellipse { start, end, color, size, filled, angle } => {
+                let points: Vec<Pos2> = get_ellipse_points(*start, *end, *angle).into_iter().map(to_screen).collect();
+                if *filled {
+                    painter.add(Shape::convex_polygon(points, to_color32(*color), Stroke::NONE));
+                }
+                else {
+                    painter.add(Shape::line(points, Stroke::new((size * 2 + 1) as f32 * scale, to_color32(*color))));
+                }
+            }
+            Annotation::Arrow { start, end, color, size, heads, style } => {
+                let width = (size * 2 + 1) as f32;
+                let stroke = Stroke::new(width * scale, to_color32(*color));
+
+                let geometry = get_arrow_geometry(*start, *end, width, *heads, *style);
+                painter.line_segment([to_screen(geometry.shaft.0), to_screen(geometry.shaft.1)], stroke);
+                for head in geometry.heads.iter() {
+                    let points: Vec<Pos2> = head.iter().map(|point| to_screen(*point)).collect();
+                    match style {
+                        ArrowStyle::Filled => painter.add(Shape::convex_polygon(points, to_color32(*color), Stroke::NONE)),
+                        ArrowStyle::Open => painter.add(Shape::line(points, stroke)),
+                    };
+                }
+            }
+            Annotation::Text { pos, text, fonts, family, size, color, background } => {
+                let (width, height) = get_text_size(text, fonts, *size);
+                let min = to_screen(*pos);
+                if let Some(background) = background {
+                    painter.rect_filled(Rect::from_min_size(min, Vec2::new(width, height) * scale), 0., to_color32(*background));
+                }
+
+                let padding = size * 0.2 * scale;
+                painter.text(min + Vec2::splat(padding), Align2::LEFT_TOP, text, FontId::new(size * scale, family.clone()), to_color32(*color));
+            }
+            Annotation::Step { center, radius, number, family, color, .. } => {
+                let (text_size, text_color) = get_step_marker_style(*number, *radius, *color);
+                painter.circle_filled(to_screen(*center), radius * scale, to_color32(*color));
+                painter.text(to_screen(*center), Align2::CENTER_CENTER, number.to_string(), FontId::new(text_size * scale, family.clone()), to_color32(text_color));
+            }
+        }
+    }
+
+    // Box around the annotation before it is turned by angle(), which happens around its center
+    pub fn bounds(&self) -> Rect {
+        let to_pos = |point: &(f32, f32)| Pos2::new(point.0, point.1);
+
+        match self {
+            Annotation::Stroke { points, .. } | Annotation::Erase { points, .. } => {
+                let points: Vec<Pos2> = points.iter().map(to_pos).collect();
+                Rect::from_points(&points)
+            }
+            Annotation::Rectangle { start, end, .. } | Annotation::Ellipse { start, end, .. } | Annotation::Arrow { start, end, .. } => {
+                Rect::from_two_pos(to_pos(start), to_pos(end))
+            }
+            Annotation::Text { pos, text, fonts, size, .. } => {
+                let (width, height) = get_text_size(text, fonts, *size);
+                Rect::from_min_size(to_pos(pos), Vec2::new(width, height))
+            }
+            Annotation::Step { center, radius, .. } => Rect::from_center_size(to_pos(center), Vec2::splat(radius * 2.)),
+        }
+    }
+
+    pub fn angle(&self) -> f32 {
+        match self {
+            Annotation::Rectangle { angle, .. } | Annotation::Ellipse { angle, .. } => *angle,
+            _ => 0.,
+        }
+    }
+
+    // Text and step markers are always drawn upright
+    pub fn can_rotate(&self) -> bool {
+        match self {
+            Annotation::Text { .. } | Annotation::Step { .. } => false,
+            _ => true,
+        }
+    }
+
+    // Whether point is on the visible part of the annotation, or less than tolerance pixels from it
+    pub fn hit_test(&self, point: (f32, f32), tolerance: f32) -> bool {
+        let center = self.bounds().center();
+        let local = rotate_point(point, (center.x, center.y), -self.angle());
+        let local_pos = Pos2::new(local.0, local.1);
+
+        match self {
+            Annotation::Stroke { points, width, .. } | Annotation::Erase { points, width } => {
+                let radius = width / 2. + tolerance;
+                if points.len() == 1 {
+                    return local_pos.distance(Pos2::new(points[0].0, points[0].1)) <= radius;
+                }
+                points.windows(2).any(|segment| distance_to_segment(point, segment[0], segment[1]) <= radius)
+            }
+            Annotation::Rectangle { size, filled, .. } => {
+                let bounds = self.bounds();
+                if *filled {
+                    return bounds.expand(tolerance).contains(local_pos);
+                }
+                let radius = (size * 2 + 1) as f32 / 2. + tolerance;
+                bounds.expand(radius).contains(local_pos) && !bounds.shrink(radius).contains(local_pos)
+            }
+            Annotation::Ellipse { start, end, size, filled, .. } => {
+                if *filled {
+                    let radius = self.bounds().size() / 2. + Vec2::splat(tolerance);
+                    let offset = local_pos - center;
+                    return (offset.x / radius.x).powi(2) + (offset.y / radius.y).powi(2) <= 1.;
+                }
+                let radius = (size * 2 + 1) as f32 / 2. + tolerance;
+                get_ellipse_points(*start, *end, 0.).windows(2).any(|segment| distance_to_segment(local, segment[0], segment[1]) <= radius)
+            }
+            Annotation::Arrow { start, end, size, .. } => {
+                distance_to_segment(point, *start, *end) <= (size * 2 + 1) as f32 / 2. + tolerance
+            }
+            Annotation::Text { .. } => self.bounds().expand(tolerance).contains(local_pos),
+            Annotation::Step { center, radius, .. } => local_pos.distance(Pos2::new(center.0, center.1)) <= radius + tolerance,
+        }
+    }
+
+    // Stretches the annotation so that the from box, usually its bounds, becomes the to box.
+    // Line widths are kept, text and step markers are scaled without being stretched.
+    pub fn resize(&mut self, from: Rect, to: Rect) {
+        let scale_x = if from.width() > 0. { to.width() / from.width() } else { 1. };
+        let scale_y = if from.height() > 0. { to.height() / from.height() } else { 1. };
+        let apply = |point: &mut (f32, f32)| {
+            point.0 = to.min.x + (point.0 - from.min.x) * scale_x;
+            point.1 = to.min.y + (point.1 - from.min.y) * scale_y;
+        };
+
+        match self {
+            Annotation::Stroke { points, .. } | Annotation::Erase { points, .. } => points.iter_mut().for_each(apply),
+            Annotation::Rectangle { start, end, .. } | Annotation::Ellipse { start, end, .. } | Annotation::Arrow { start, end, .. } => {
+                apply(start);
+                apply(end);
+            }
+            Annotation::Text { pos, size, .. } => {
+                apply(pos);
+                *size = (*size * scale_y).max(1.);
+            }
+            Annotation::Step { center, radius, .. } => {
+                apply(center);
+                *radius = (*radius * scale_x.min(scale_y)).max(1.);
+            }
+        }
+    }
+
+    // Turns the annotation by angle radians around center, see can_rotate
+    pub fn rotate(&mut self, center: (f32, f32), angle: f32) {
+        let apply = |point: &mut (f32, f32)| *point = rotate_point(*point, center, angle);
+
+        match self {
+            Annotation::Stroke { points, .. } | Annotation::Erase { points, .. } => points.iter_mut().for_each(apply),
+            Annotation::Arrow { start, end, .. } => {
+                apply(start);
+                apply(end);
+            }
+            Annotation::Rectangle { start, end, angle: shape_angle, .. } | Annotation::Ellipse { start, end, angle: shape_angle, .. } => {
+                // The shape turns around its own center, which moves around center
+                let shape_center = ((start.0 + end.0) / 2., (start.1 + end.1) / 2.);
+                let new_center = rotate_point(shape_center, center, angle);
+                for point in [start, end] {
+                    point.0 += new_center.0 - shape_center.0;
+                    point.1 += new_center.1 - shape_center.1;
+                }
+                *shape_angle += angle;
+            }
+            Annotation::Text { .. } | Annotation::Step { .. } => {}
+        }
+    }
+
+    // Scales the annotation with the capture it is drawn on. Line widths, text and step markers follow
+    // the smaller of the two factors.
+    pub fn scale(&mut self, scale: (f32, f32)) {
+        let factor = scale.0.min(scale.1);
+        let apply = |point: &mut (f32, f32)| {
+            point.0 *= scale.0;
+            point.1 *= scale.1;
+        };
+        // Shapes are drawn size * 2 + 1 pixels wide
+        let scale_size = |size: &mut i32| *size = (((*size * 2 + 1) as f32 * factor - 1.) / 2.).round().max(0.) as i32;
+
+        match self {
+            Annotation::Stroke { points, width, .. } | Annotation::Erase { points, width } => {
+                points.iter_mut().for_each(apply);
+                *width = (*width * factor).max(1.);
+            }
+            Annotation::Rectangle { start, end, size, .. } | Annotation::Ellipse { start, end, size, .. } | Annotation::Arrow { start, end, size, .. } => {
+                apply(start);
+                apply(end);
+                scale_size(size);
+            }
+            Annotation::Text { pos, size, .. } => {
+                apply(pos);
+                *size = (*size * factor).max(1.);
+            }
+            Annotation::Step { center, radius, .. } => {
+                apply(center);
+                *radius = (*radius * factor).max(1.);
+            }
+        }
+    }
+
+    // Follows the capture, of the given size before the transform, when it is rotated or flipped.
+    // Text and step markers stay upright.
+    pub fn transform(&mut self, transform: Transform, image_size: (f32, f32)) {
+        let apply = |point: &mut (f32, f32)| *point = transform.apply_to_point(*point, image_size);
+
+        match self {
+            Annotation::Stroke { points, .. } | Annotation::Erase { points, .. } => points.iter_mut().for_each(apply),
+            Annotation::Arrow { start, end, .. } => {
+                apply(start);
+                apply(end);
+            }
+            Annotation::Rectangle { start, end, angle, .. } | Annotation::Ellipse { start, end, angle, .. } => {
+                apply(start);
+                apply(end);
+                if transform.is_flip() {
+                    *angle = -*angle;
+                }
+            }
+            Annotation::Text { .. } => {
+                let bounds = self.bounds();
+                let center = transform.apply_to_point(bounds.center().into(), image_size);
+                if let Annotation::Text { pos, .. } = self {
+                    *pos = (center.0 - bounds.width() / 2., center.1 - bounds.height() / 2.);
+                }
+            }
+            Annotation::Step { center, .. } => apply(center),
+        }
+    }
+
+    // Moves every point of the annotation, used when the capture is cropped
+    pub fn translate(&mut self, offset: (f32, f32)) {
+        let apply = |point: &mut (f32, f32)| {
+            point.0 += offset.0;
+            point.1 += offset.1;
+        };
+
+        match self {
+            Annotation::Stroke { points, .. } | Annotation::Erase { points, .. } => points.iter_mut().for_each(apply),
+            Annotation::Rectangle { start, end, .. } | Annotation::Ellipse { start, end, .. } | Annotation::Arrow { start, end, .. } => {
+                apply(start);
+                apply(end);
+            }
+            Annotation::Text { pos, .. } => apply(pos),
+            Annotation::Step { center, .. } => apply(center),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn get_rectangle(start: (f32, f32), end: (f32, f32), filled: bool, angle: f32) -> Annotation {
+        return Annotation::Rectangle { start: start, end: end, color: [255, 0, 0, 255], size: 1, filled: filled, angle: angle };
+    }
+
+    fn assert_near(a: (f32, f32), b: (f32, f32)) {
+        assert!((a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn hits_the_outline_or_the_inside_of_filled_shapes() {
+        let outline = get_rectangle((10., 10.), (50., 30.), false, 0.);
+        let filled = get_rectangle((10., 10.), (50., 30.), true, 0.);
+
+        assert!(outline.hit_test((10., 20.), 0.));
+        assert!(!outline.hit_test((30., 20.), 0.));
+        assert!(filled.hit_test((30., 20.), 0.));
+        assert!(!filled.hit_test((60., 20.), 0.));
+
+        // The outline is 3 pixels wide, 4 pixels away only hits with some tolerance
+        assert!(!outline.hit_test((6., 20.), 0.));
+        assert!(outline.hit_test((6., 20.), 3.));
+        assert!(!filled.hit_test((6., 20.), 3.));
+        assert!(filled.hit_test((6., 20.), 4.));
+    }
+
+    #[test]
+    fn hits_rotated_shapes_where_they_are_drawn() {
+        // 100x20 around (50, 50), turned upright
+        let rectangle = get_rectangle((0., 40.), (100., 60.), true, FRAC_PI_2);
+
+        assert!(rectangle.hit_test((50., 5.), 0.));
+        assert!(rectangle.hit_test((50., 95.), 0.));
+        assert!(!rectangle.hit_test((5., 50.), 0.));
+        assert!(!rectangle.hit_test((95., 50.), 0.));
+    }
+
+    #[test]
+    fn resizes_from_the_dragged_side() {
+        let mut rectangle = get_rectangle((10., 10.), (50., 30.), false, 0.);
+        let bounds = rectangle.bounds();
+
+        // Dragging the right side keeps the left one
+        rectangle.resize(bounds, Rect::from_min_max(bounds.min, Pos2::new(90., 30.)));
+        assert_eq!(rectangle.bounds(), Rect::from_min_max(Pos2::new(10., 10.), Pos2::new(90., 30.)));
+
+        // Dragging the top left corner keeps the bottom right one
+        let bounds = rectangle.bounds();
+        rectangle.resize(bounds, Rect::from_min_max(Pos2::new(0., 20.), bounds.max));
+        assert_eq!(rectangle.bounds(), Rect::from_min_max(Pos2::new(0., 20.), Pos2::new(90., 30.)));
+
+        // Step markers follow the box without being stretched
+        let mut step = Annotation::Step { center: (20., 20.), radius: 10., number: 1, fonts: Vec::new(), family: FontFamily::Proportional, color: [255, 0, 0, 255] };
+        let bounds = step.bounds();
+        step.resize(bounds, Rect::from_min_max(bounds.min, Pos2::new(50., 30.)));
+        match step {
+            Annotation::Step { center, radius, .. } => {
+                assert_eq!(center, (30., 20.));
+                assert_eq!(radius, 10.);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn rotates_around_a_center() {
+        let mut arrow = Annotation::Arrow { start: (10., 0.), end: (20., 0.), color: [255, 0, 0, 255], size: 1, heads: ArrowHeads::Single, style: ArrowStyle::Open };
+        arrow.rotate((0., 0.), FRAC_PI_2);
+        match arrow {
+            Annotation::Arrow { start, end, .. } => {
+                assert_near(start, (0., 10.));
+                assert_near(end, (0., 20.));
+            }
+            _ => unreachable!(),
+        }
+
+        // Rectangles keep their size, their center moves and they turn by the angle
+        let mut rectangle = get_rectangle((10., -5.), (30., 5.), false, 0.);
+        rectangle.rotate((0., 0.), FRAC_PI_2);
+        let bounds = rectangle.bounds();
+        assert_near(bounds.center().into(), (0., 20.));
+        assert_near(bounds.size().into(), (20., 10.));
+        assert_eq!(rectangle.angle(), FRAC_PI_2);
+        assert!(rectangle.hit_test((0., 29.), 0.));
+        assert!(!rectangle.hit_test((9., 20.), 0.));
+    }
+
+    #[test]
+    fn translates_every_point() {
+        let mut stroke = Annotation::Stroke { points: vec![(0., 0.), (10., 5.)], color: [255, 0, 0, 255], width: 2. };
+        stroke.translate((-5., 3.));
+        match stroke {
+            Annotation::Stroke { points, .. } => assert_eq!(points, vec![(-5., 3.), (5., 8.)]),
+            _ => unreachable!(),
+        }
+
+        let mut rectangle = get_rectangle((10., 10.), (50., 30.), true, 0.5);
+        rectangle.translate((5., -10.));
+        assert_eq!(rectangle.bounds(), Rect::from_min_max(Pos2::new(15., 0.), Pos2::new(55., 20.)));
+        assert_eq!(rectangle.angle(), 0.5);
+    }
+}
@@ -1,26 +1,26 @@
-pub mod tool_utils;
-pub mod hotkeys_utils;
+use snap_rust::tool_utils::*;
+use snap_rust::hotkeys_utils::*;
+use snap_rust::capture_utils::*;
+use snap_rust::export_utils::*;
+use snap_rust::adjust_utils::*;
+use snap_rust::document::Document;
+use snap_rust::annotation::Annotation;
 
-use tool_utils::*;
-use hotkeys_utils::*;
 
-
-use eframe::egui::{Shape, Rect, Visuals, SidePanel, Sense, Pos2, Vec2, Align, Button, DragValue, CentralPanel, Context, Layout, Direction, TopBottomPanel, ComboBox, ColorImage, ImageButton, Response, CursorIcon, Ui, Stroke};
+use eframe::egui::{Painter, Shape, Rect, Align2, FontId, Key, Visuals, SidePanel, Window, ScrollArea, Sense, Pos2, Vec2, Align, Button, DragValue, Slider, CentralPanel, Context, Layout, Direction, TopBottomPanel, ComboBox, Grid, LayerId, Order, Id, FontFamily, FontDefinitions, FontData, ColorImage, ImageButton, TextureId, TextureHandle, TextureOptions, PointerButton, Response, CursorIcon, Ui, Stroke, TextEdit};
 use eframe::Frame;
 
 use egui::{Color32, RichText};
 use rfd::FileDialog;
 
-use screenshots::display_info::DisplayInfo;
-use screenshots::Screen;
-use image::DynamicImage;
+use image::{DynamicImage, GenericImageView};
+use image::imageops::FilterType;
+use rusttype::Font;
 
-use std::collections::VecDeque;
 use std::thread;
+use std::sync::Arc;
 use std::sync::mpsc::{channel, Receiver, Sender};
-use std::time::Duration;
-
-use chrono::Local;
+use std::time::{Duration, Instant};
 
 use global_hotkey::{GlobalHotKeyManager, HotKeyState, GlobalHotKeyEvent};
 
@@ -28,14 +28,58 @@ use arboard::{Clipboard, ImageData};
 use std::borrow::Cow;
 
 use std::collections::HashSet;
-
+use std::path::Path;
+
+
+pub const MIN_WINDOW_SIZE: Vec2 = Vec2::new(750., 500.);
+// The native minimum size is lowered to this so the window can shrink into the countdown indicator
+pub const COUNTDOWN_WINDOW_SIZE: Vec2 = Vec2::new(180., 100.);
+const HIGHLIGHTER_ALPHA: u8 = 100;
+const PEN_SMOOTHING: f32 = 0.6;
+// Pixels shown on each side of the sampled one and their size in the loupe
+const LOUPE_RADIUS: i64 = 5;
+const LOUPE_CELL_SIZE: f32 = 10.;
+// Screen pixels around the selection handles and annotations that still pick them
+const SELECTION_TOLERANCE: f32 = 5.;
+// Longest side of the copy of the snapshot the beautify preview is made from
+const BEAUTIFY_PREVIEW_SIZE: f32 = 480.;
+// Longest side of the copy of the snapshot the adjustments are previewed on while dragging
+const ADJUST_PROXY_SIZE: f32 = 1024.;
+// Zoom limits in screen pixels per image pixel, and the zoom change for each point of scrolling
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 32.;
+const ZOOM_SPEED: f32 = 0.005;
+
+// Side panel settings that are copied to the selected annotation when they change
+#[derive(PartialEq, Clone)]
+struct AnnotationStyle {
+    pen_color: [f32; 3],
+    pen_size: usize,
+    shape_filled: bool,
+    arrow_heads: ArrowHeads,
+    arrow_style: ArrowStyle,
+    highlighter_color: [f32; 3],
+    highlighter_size: usize,
+    eraser_size: usize,
+    step_size: f32,
+    text_size: f32,
+    text_background: bool,
+    text_background_color: [f32; 3],
+    font_family: FontFamily,
+}
 
 pub struct SnapRustApp {
-    snapshot: Option<DynamicImage>,
-    snapshots_undo: VecDeque<DynamicImage>,
-    snapshots_redo: VecDeque<DynamicImage>,
+    document: Option<Document>,
+    capture_source: Arc<dyn CaptureSource>,
     display: Option<usize>,
     timer: Option<f64>,
+    countdown: Option<Instant>,
+    window_geometry: Option<(Option<Pos2>, Vec2)>,
+    capture_mode: CaptureMode,
+    window: Option<WindowInfo>,
+    window_decorations: bool,
+    pending_region: bool,
+    region_selection: Option<RegionSelection>,
     show_settings: bool,
     show_credits: bool,
     show_tools: bool,
@@ -43,10 +87,70 @@ pub struct SnapRustApp {
     tooling: bool,
     pen_color: [f32; 3],
     pen_size: usize,
+    pen_smoothing: bool,
+    shape_filled: bool,
+    highlighter_color: [f32; 3],
+    highlighter_size: usize,
+    eraser_size: usize,
+    step_size: f32,
+    color_format: ColorFormat,
+    picked_color: Option<[u8; 3]>,
+    redact_style: RedactStyle,
+    redact_strength: u32,
+    redact_color: [f32; 3],
+    arrow_heads: ArrowHeads,
+    arrow_style: ArrowStyle,
+    text_box: Option<TextBox>,
+    text_size: f32,
+    text_background: bool,
+    text_background_color: [f32; 3],
+    // Main font followed by the fallback fonts
+    fonts: Vec<Font<'static>>,
+    font_name: String,
+    // egui fonts with the loaded fonts added, font_family shows the current one on screen
+    font_definitions: FontDefinitions,
+    font_family: FontFamily,
+    // Points of the pen, highlighter or eraser stroke being drawn, in image pixels
+    stroke_points: Vec<(f32, f32)>,
+    // Index of the selected annotation
+    selection: Option<usize>,
+    // Handle being dragged, the annotation before the drag and where the drag started in image pixels
+    selection_drag: Option<(SelectionHandle, Annotation, Pos2)>,
+    // Set while a side panel setting is dragged, so the changes it makes to the selection are undone at once
+    restyling: bool,
+    // Area to crop in image pixels, waiting to be applied
+    crop_rect: Option<Rect>,
+    // Handle being dragged, the area before the drag and where the drag started in image pixels
+    crop_drag: Option<(SelectionHandle, Rect, Pos2)>,
+    crop_aspect: AspectRatio,
+    resize_dialog: Option<ResizeDialog>,
+    beautify: BeautifySettings,
+    show_beautify: bool,
+    // Preview texture and the settings and document revision it was made with
+    beautify_preview: Option<(BeautifySettings, u64, TextureHandle)>,
+    show_adjustments: bool,
+    // Shows the snapshot without the adjustments
+    show_before: bool,
+    // Adjustments being dragged in the panel, only given to the document once the pointer is released
+    adjust_pending: Option<Adjustments>,
+    // Small copy of the background with the document revision it was made at, and the pending
+    // adjustments applied to it
+    adjust_proxy: Option<(u64, DynamicImage)>,
+    adjust_preview: Option<(Adjustments, DynamicImage)>,
+    // Scale factor of the display of the capture being taken
+    pending_scale_factor: f32,
+    // Where the drag of a tool started and where it is now, in image pixels
     last_pos: Pos2,
     current_pos: Pos2,
-    rx: Receiver<DynamicImage>,
-    tx: Sender<DynamicImage>,
+    // Size of an image pixel in screen pixels, None fits the whole snapshot in the panel
+    zoom: Option<f32>,
+    // Offset of the image center from the panel center, in points
+    pan: Vec2,
+    // Set from the press that starts a pan until the button is released, the tools ignore that drag
+    panning: bool,
+    capture_error: Option<CaptureError>,
+    rx: Receiver<Result<DynamicImage, CaptureError>>,
+    tx: Sender<Result<DynamicImage, CaptureError>>,
     hotkeys: Vec<Hotkey>,
     valid_hotkeys: bool,
     manager: GlobalHotKeyManager,
@@ -60,18 +164,30 @@ impl Default for SnapRustApp {
         hotkeys_vec.push(Hotkey::new("Copy".to_string(), "CTRL".to_string(), "C".to_string()));
         hotkeys_vec.push(Hotkey::new("Save".to_string(), "CTRL".to_string(), "S".to_string()));
         hotkeys_vec.push(Hotkey::new("Take".to_string(), "CTRL".to_string(), "T".to_string()));
+        hotkeys_vec.push(Hotkey::new("Cancel".to_string(), "CTRL".to_string(), "Q".to_string()));
         hotkeys_vec.push(Hotkey::new("None".to_string(), "CTRL".to_string(), "N".to_string()));
         hotkeys_vec.push(Hotkey::new("Pen".to_string(), "CTRL".to_string(), "P".to_string()));
         hotkeys_vec.push(Hotkey::new("Crop".to_string(), "CTRL".to_string(), "X".to_string()));
         hotkeys_vec.push(Hotkey::new("Undo".to_string(), "CTRL".to_string(), "Z".to_string()));
         hotkeys_vec.push(Hotkey::new("Redo".to_string(), "CTRL".to_string(), "Y".to_string()));
+        hotkeys_vec.push(Hotkey::new("Rotate left".to_string(), "CTRL".to_string(), "L".to_string()));
+        hotkeys_vec.push(Hotkey::new("Rotate right".to_string(), "CTRL".to_string(), "R".to_string()));
+        hotkeys_vec.push(Hotkey::new("Rotate 180".to_string(), "CTRL".to_string(), "U".to_string()));
+        hotkeys_vec.push(Hotkey::new("Flip horizontal".to_string(), "CTRL".to_string(), "H".to_string()));
+        hotkeys_vec.push(Hotkey::new("Flip vertical".to_string(), "CTRL".to_string(), "J".to_string()));
 
         SnapRustApp {
-            snapshot: None,
-            snapshots_undo: VecDeque::new(),
-            snapshots_redo: VecDeque::new(),
+            document: None,
+            capture_source: get_capture_source(),
             display: Some(0),
             timer: Some(0.),
+            countdown: None,
+            window_geometry: None,
+            capture_mode: CaptureMode::Display,
+            window: None,
+            window_decorations: true,
+            pending_region: false,
+            region_selection: None,
             show_settings: false,
             show_credits: false,
             show_tools: false,
@@ -79,8 +195,50 @@ impl Default for SnapRustApp {
             tooling: false,
             pen_color: [0.9, 0.3, 0.24],
             pen_size: 1,
+            pen_smoothing: false,
+            shape_filled: false,
+            highlighter_color: [1., 0.85, 0.],
+            highlighter_size: 10,
+            eraser_size: 10,
+            step_size: 16.,
+            color_format: ColorFormat::Hex,
+            picked_color: None,
+            redact_style: RedactStyle::Pixelate,
+            redact_strength: 12,
+            redact_color: [0., 0., 0.],
+            arrow_heads: ArrowHeads::Single,
+            arrow_style: ArrowStyle::Filled,
+            text_box: None,
+            text_size: 24.,
+            text_background: false,
+            text_background_color: [1., 1., 1.],
+            fonts: get_default_fonts(),
+            font_name: "Default".to_string(),
+            font_definitions: FontDefinitions::default(),
+            font_family: FontFamily::Proportional,
+            stroke_points: Vec::new(),
+            selection: None,
+            selection_drag: None,
+            restyling: false,
+            crop_rect: None,
+            crop_drag: None,
+            crop_aspect: AspectRatio::Free,
+            resize_dialog: None,
+            beautify: BeautifySettings::default(),
+            show_beautify: false,
+            beautify_preview: None,
+            show_adjustments: false,
+            show_before: false,
+            adjust_pending: None,
+            adjust_proxy: None,
+            adjust_preview: None,
+            pending_scale_factor: 1.,
             last_pos: Pos2::default(),
             current_pos: Pos2::default(),
+            zoom: None,
+            pan: Vec2::ZERO,
+            panning: false,
+            capture_error: None,
             rx: rx,
             tx: tx,
             hotkeys: hotkeys_vec,
@@ -132,44 +290,75 @@ impl SnapRustApp {
                 match action {
                     Some(action_value) => {
                         if action_value =="Take".to_string() {
-                            frame.set_visible(false);
-                            self.get_snapshot(ctx);
+                            if self.countdown.is_none() {
+                                self.start_snapshot(ctx, frame);
+                            }
+                        }
+                        else if action_value =="Cancel".to_string() {
+                            if self.countdown.is_some() {
+                                self.end_countdown(frame);
+                            }
                         }
                         else if action_value =="Save".to_string() {
-                            if self.snapshot.is_some(){
+                            if self.document.is_some(){
                                 self.save_snapshot();
                             }
                         }
                         else if action_value =="Copy".to_string() {
-                            if self.snapshot.is_some(){
+                            if self.document.is_some(){
                                 self.copy_snapshot();
                             }
                         }
                         else if action_value =="None".to_string() {
-                            if self.snapshot.is_some(){
+                            if self.document.is_some(){
                                 self.tool = Tool::None;
                             }
                         }
                         else if action_value =="Pen".to_string() {
-                            if self.snapshot.is_some(){
+                            if self.document.is_some(){
                                 self.tool = Tool::Pen;
                             }
                         }
                         else if action_value =="Crop".to_string() {
-                            if self.snapshot.is_some(){
+                            if self.document.is_some(){
                                 self.tool = Tool::Crop;
                             }
                         }
                         else if action_value =="Undo".to_string() {
-                            if self.snapshot.is_some(){
+                            if self.document.is_some(){
                                 self.undo();
                             }
                         }
                         else if action_value =="Redo".to_string() {
-                            if self.snapshot.is_some(){
+                            if self.document.is_some(){
                                 self.redo();
                             }
                         }
+                        else if action_value =="Rotate left".to_string() {
+                            if self.document.is_some(){
+                                self.transform(Transform::RotateLeft);
+                            }
+                        }
+                        else if action_value =="Rotate right".to_string() {
+                            if self.document.is_some(){
+                                self.transform(Transform::RotateRight);
+                            }
+                        }
+                        else if action_value =="Rotate 180".to_string() {
+                            if self.document.is_some(){
+                                self.transform(Transform::Rotate180);
+                            }
+                        }
+                        else if action_value =="Flip horizontal".to_string() {
+                            if self.document.is_some(){
+                                self.transform(Transform::FlipHorizontal);
+                            }
+                        }
+                        else if action_value =="Flip vertical".to_string() {
+                            if self.document.is_some(){
+                                self.transform(Transform::FlipVertical);
+                            }
+                        }
                     }
                     None => {
                         println!("No hotkey available");
@@ -181,29 +370,111 @@ impl SnapRustApp {
     }
 
 
+    fn start_snapshot(&mut self, ctx: &Context, frame: &mut Frame) {
+        let timer = self.timer.unwrap();
+
+        if timer > 0. {
+            // Shrink the window into a small always on top indicator while counting down
+            let window_info = frame.info().window_info;
+            self.window_geometry = Some((window_info.position, window_info.size));
+
+            frame.set_decorations(false);
+            frame.set_always_on_top(true);
+            frame.set_window_size(COUNTDOWN_WINDOW_SIZE);
+            if let Some(monitor_size) = window_info.monitor_size {
+                frame.set_window_pos(Pos2::new(monitor_size.x - COUNTDOWN_WINDOW_SIZE.x - 20., 20.));
+            }
+
+            self.countdown = Some(Instant::now() + Duration::from_secs_f64(timer));
+        }
+        else {
+            frame.set_visible(false);
+            self.get_snapshot(ctx);
+        }
+    }
+
+    fn end_countdown(&mut self, frame: &mut Frame) {
+        if let Some((position, size)) = self.window_geometry {
+            if let Some(position) = position {
+                frame.set_window_pos(position);
+            }
+            frame.set_window_size(size);
+        }
+
+        frame.set_decorations(true);
+        frame.set_always_on_top(false);
+
+        self.countdown = None;
+    }
+
+    fn enforce_min_window_size(&mut self, frame: &mut Frame) {
+        if self.countdown.is_some() {
+            return;
+        }
+
+        let size = frame.info().window_info.size;
+        let too_small = size.x < MIN_WINDOW_SIZE.x || size.y < MIN_WINDOW_SIZE.y;
+
+        // Wait for the size saved before the countdown to be restored
+        if self.window_geometry.is_some() {
+            if !too_small {
+                self.window_geometry = None;
+            }
+        }
+        else if too_small {
+            frame.set_window_size(size.max(MIN_WINDOW_SIZE));
+        }
+    }
+
     fn get_snapshot(&mut self, ctx: &Context) {
    
-        let display = self.display.unwrap().clone();
-        let mut timer = self.timer.unwrap().clone();
+        let display = self.display;
+        // Give the window time to hide before capturing
+        let timer = 0.35;
 
-        if timer < 1.{
-            timer += 0.35;
-        }
+        self.pending_region = self.capture_mode == CaptureMode::Region;
+        self.capture_error = None;
+
+        let window = match self.capture_mode {
+            CaptureMode::Window => self.window.as_ref().map(|window| window.id),
+            _ => None,
+        };
+        let window_decorations = self.window_decorations;
+
+        let scale_region = match self.window.as_ref().filter(|_| window.is_some()) {
+            Some(window_info) => {
+                let (x, y, width, height) = window_info.get_area(window_decorations);
+                CaptureRegion::Area(x, y, width, height)
+            }
+            None => match display {
+                Some(display) => CaptureRegion::Display(display),
+                None => CaptureRegion::AllDisplays,
+            }
+        };
+        self.pending_scale_factor = get_capture_scale_factor(&self.capture_source.list_displays().unwrap_or_default(), scale_region);
 
+        let capture_source = self.capture_source.clone();
         let tx = self.tx.clone();
         let context = ctx.clone();
 
         thread::spawn(move || {
-            let display_info = match DisplayInfo::all() {
-                Ok(display_vec) => Some(display_vec[display]),
-                Err(_) => panic!("Invalid screen choice")
-            };
-
             thread::sleep(Duration::from_millis((timer * 1000.0) as u64));
 
-            let screen : Screen = Screen::new(&display_info.unwrap());
-            let image_buffer = screen.capture().unwrap();
-            let snapshot = DynamicImage::from(image_buffer);
+            let region = match window {
+                Some(window_id) => {
+                    // Look the window up again, it may have moved during the timer
+                    get_window(window_id)
+                        .map(|window_info| window_info.get_area(window_decorations))
+                        .map(|(x, y, width, height)| CaptureRegion::Area(x, y, width, height))
+                        .ok_or(CaptureError::WindowNotFound)
+                }
+                None => match display {
+                    Some(display) => Ok(CaptureRegion::Display(display)),
+                    None => Ok(CaptureRegion::AllDisplays),
+                }
+            };
+
+            let snapshot = region.and_then(|region| capture_source.capture(region));
 
             tx.send(snapshot).ok();
             context.request_repaint();
@@ -212,12 +483,19 @@ impl SnapRustApp {
 
     
 
-    fn save_snapshot(&mut self) {
+    // The snapshot with its annotations, framed if beautify is enabled
+    fn get_export_image(&self) -> DynamicImage {
+        let image = self.document.as_ref().unwrap().flatten();
+        if self.beautify.enabled {
+            return beautify_image(&image, &self.beautify, 1.);
+        }
+        return image;
+    }
 
-        let formatted_date = Local::now().format("%Y_%m_%d_%H_%M_%S").to_string();
+    fn save_snapshot(&mut self) {
 
         let path = FileDialog::new()
-            .set_file_name("snapshot_".to_string() + &formatted_date)
+            .set_file_name(get_default_file_name())
             .add_filter("PNG", &["png"])
             .add_filter("JPG", &["jpg"])
             .add_filter("GIF", &["gif"])
@@ -226,15 +504,7 @@ impl SnapRustApp {
 
         match path {
             Some(path) => {
-                let snapshot = self.snapshot.as_ref().unwrap();
-
-                match image::save_buffer(
-                    path,
-                    &snapshot.as_bytes(),
-                    snapshot.width() as u32,
-                    snapshot.height() as u32,
-                    image::ColorType::Rgba8,
-                ) {
+                match save_image(&path, &self.get_export_image()) {
                     Ok(_) => {},
                     Err(err) => println!("{}", err),
                 }
@@ -245,282 +515,1315 @@ impl SnapRustApp {
 
     fn copy_snapshot(&mut self) {
         let mut clipboard = Clipboard::new().unwrap();
-        let snapshot = self.snapshot.as_ref().unwrap();
+        let snapshot = self.get_export_image();
         let image = ImageData{
             width: snapshot.width() as usize,
             height: snapshot.height() as usize,
-            bytes: Cow::from(snapshot.as_bytes())
+            bytes: Cow::from(snapshot.to_rgba8().into_raw())
         };
         clipboard.set_image(image).ok();
     }
 
+    fn start_region_selection(&mut self, frame: &mut Frame, frozen: DynamicImage) {
+        let display_vec = self.capture_source.list_displays().unwrap_or_default();
+        match self.display {
+            Some(display) => {
+                if let Some(display_info) = display_vec.get(display) {
+                    frame.set_window_pos(Pos2::new(display_info.x as f32, display_info.y as f32));
+                }
+            }
+            None => {
+                let min_x = display_vec.iter().map(|display_info| display_info.x).min().unwrap_or(0);
+                let min_y = display_vec.iter().map(|display_info| display_info.y).min().unwrap_or(0);
+                frame.set_window_pos(Pos2::new(min_x as f32, min_y as f32));
+            }
+        }
+
+        frame.set_decorations(false);
+        frame.set_fullscreen(true);
+        frame.set_always_on_top(true);
+
+        self.region_selection = Some(RegionSelection::new(frozen));
+    }
+
+    fn end_region_selection(&mut self, frame: &mut Frame) {
+        frame.set_fullscreen(false);
+        frame.set_decorations(true);
+        frame.set_always_on_top(false);
+
+        self.region_selection = None;
+    }
+
     fn undo(&mut self) {
-        if self.snapshots_undo.len()>1{
-            self.snapshots_redo.push_front(self.snapshots_undo.pop_back().unwrap());
-            self.snapshot = Some(self.snapshots_undo.get(self.snapshots_undo.len()-1).unwrap().clone());
+        self.selection = None;
+        if let Some(document) = self.document.as_mut() {
+            document.undo();
         }
     }
 
     fn redo(&mut self) {
-        if self.snapshots_redo.len()>0{
-            self.snapshots_undo.push_back(self.snapshots_redo.pop_front().unwrap());
-            self.snapshot = Some(self.snapshots_undo.get(self.snapshots_undo.len()-1).unwrap().clone());
+        self.selection = None;
+        if let Some(document) = self.document.as_mut() {
+            document.redo();
         }
     }
 
+    // Rotates or flips the capture, with the annotations and the pending crop
+    fn transform(&mut self, transform: Transform) {
+        let document = self.document.as_mut().unwrap();
+        let size = (document.background().width() as f32, document.background().height() as f32);
+        document.transform(transform);
 
-    fn update_editing(&mut self, ui: &mut Ui, image_response: Response, real_image_size: [usize; 2]) {
-        if image_response.dragged(){
-            // Update last and current position
-            if !self.tooling {
-                self.last_pos = match image_response.hover_pos() {
-                    Some(pos) => (pos - image_response.rect.left_top()).to_pos2(),
-                    None => self.last_pos,
-                };
-                self.tooling = true;
-            }
+        self.crop_rect = self.crop_rect.map(|rect| {
+            let min = transform.apply_to_point(rect.min.into(), size);
+            let max = transform.apply_to_point(rect.max.into(), size);
+            Rect::from_two_pos(min.into(), max.into())
+        });
+        self.selection_drag = None;
+        self.crop_drag = None;
+        self.resize_dialog = None;
+    }
 
-            self.current_pos = match image_response.hover_pos() {
-                Some(pos) => (pos - image_response.rect.left_top()).to_pos2(),
-                None => self.current_pos,
-            };
+    fn get_text_colors(&self) -> ([u8; 4], Option<[u8; 4]>) {
+        let rgb_color = linear_to_srgb(self.pen_color);
+        let color = [rgb_color[0], rgb_color[1], rgb_color[2], 255];
 
-            // Apply tool
-            if self.tool == Tool::Pen {
-                let image_last_pos = get_real_image_pos(self.last_pos, image_response.rect.size(), real_image_size);
-                let image_current_pos =  get_real_image_pos(self.current_pos, image_response.rect.size(), real_image_size);
-                
-                if self.tool == Tool::Pen {
-                    let rgb_color =  linear_to_srgb(self.pen_color);
-                    let color = [rgb_color[0], rgb_color[1], rgb_color[2], 255];
-                    draw_line(&mut self.snapshot.as_mut().unwrap(),
-                        image_last_pos.into(),
-                        image_current_pos.into(),
-                        self.pen_size as i32,
-                        color.into()
-                    );
-                }
-                
-                self.last_pos = self.current_pos;
+        let mut background = None;
+        if self.text_background {
+            let rgb_background = linear_to_srgb(self.text_background_color);
+            background = Some([rgb_background[0], rgb_background[1], rgb_background[2], 255]);
+        }
+
+        return (color, background);
+    }
+
+    fn commit_text(&mut self) {
+        if let Some(text_box) = self.text_box.take() {
+            if !text_box.text.trim().is_empty() {
+                let (color, background) = self.get_text_colors();
+                self.document.as_mut().unwrap().add(Annotation::Text {
+                    pos: text_box.pos.into(),
+                    text: text_box.text,
+                    fonts: self.fonts.clone(),
+                    family: self.font_family.clone(),
+                    size: self.text_size,
+                    color: color,
+                    background: background
+                });
             }
-            else if self.tool == Tool::Crop {
-                let rect = Rect::from_min_max(self.last_pos + image_response.rect.left_top().to_vec2(), self.current_pos + image_response.rect.left_top().to_vec2());
-                let stroke = Stroke::new(1., Color32::from_rgba_premultiplied(255, 255, 255, 150));
-                ui.painter().add(Shape::dashed_line(&[rect.left_top(), rect.right_top(), rect.right_bottom(), rect.left_bottom(), rect.left_top()], stroke, 6., 6.));
+        }
+    }
+
+    fn load_font(&mut self, ctx: &Context) {
+        let path = FileDialog::new()
+            .add_filter("Fonts", &["ttf", "otf"])
+            .pick_file();
+
+        if let Some(path) = path {
+            match load_font(&path) {
+                Some((font, bytes)) => {
+                    self.fonts[0] = font;
+                    self.font_name = path.file_name().unwrap().to_string_lossy().to_string();
+
+                    // Register the font with egui too, keeping the default fonts as fallbacks
+                    let name = path.to_string_lossy().to_string();
+                    let mut family_fonts = self.font_definitions.families[&FontFamily::Proportional].clone();
+                    family_fonts.insert(0, name.clone());
+                    self.font_definitions.font_data.insert(name.clone(), FontData::from_owned(bytes));
+                    self.font_family = FontFamily::Name(name.into());
+                    self.font_definitions.families.insert(self.font_family.clone(), family_fonts);
+                    ctx.set_fonts(self.font_definitions.clone());
+                }
+                None => println!("Invalid font"),
             }
+        }
+    }
 
-            
+    fn update_color_picker(&mut self, ui: &mut Ui, image_response: &Response, real_image_size: [usize; 2]) {
+        let pos = match image_response.hover_pos() {
+            Some(pos) => pos,
+            None => return,
+        };
+        let image_pos = get_real_image_pos(pos, &ViewTransform::from_rect(image_response.rect, real_image_size));
+        let x = image_pos.x.floor() as i64;
+        let y = image_pos.y.floor() as i64;
+        if x < 0 || y < 0 || x >= real_image_size[0] as i64 || y >= real_image_size[1] as i64 {
+            return;
+        }
 
-        } else if image_response.drag_released() {  
-            if self.tool == Tool::Pen {
-                self.snapshots_undo.push_back(self.snapshot.as_ref().unwrap().clone());
-                self.snapshots_redo.clear();
-            }
-            else if self.tool == Tool::Crop {
-                let image_last_pos = get_real_image_pos(self.last_pos, image_response.rect.size(), real_image_size);
-                let image_current_pos = get_real_image_pos(self.current_pos, image_response.rect.size(), real_image_size);
-                
-                let width = (image_current_pos[0] - image_last_pos[0]).abs();
-                let height = (image_current_pos[1] - image_last_pos[1]).abs();
+        let snapshot = self.document.as_ref().unwrap().adjusted();
+        let pixel = snapshot.get_pixel(x as u32, y as u32).0;
+        let color = [pixel[0], pixel[1], pixel[2]];
 
-                let mut crop_start_pos = image_last_pos;
+        // Magnified view of the pixels around the cursor, kept inside the screen
+        let loupe_size = (LOUPE_RADIUS * 2 + 1) as f32 * LOUPE_CELL_SIZE;
+        let screen_rect = ui.ctx().screen_rect();
+        let mut loupe_min = pos + Vec2::splat(20.);
+        if loupe_min.x + loupe_size > screen_rect.max.x {
+            loupe_min.x = pos.x - 20. - loupe_size;
+        }
+        if loupe_min.y + loupe_size + 24. > screen_rect.max.y {
+            loupe_min.y = pos.y - 20. - loupe_size - 24.;
+        }
 
-                if image_current_pos[0] < crop_start_pos[0]{
-                    crop_start_pos[0] -= width;
-                }
-                if image_current_pos[1] < crop_start_pos[1]{
-                    crop_start_pos[1] -= height;
+        let painter = ui.ctx().layer_painter(LayerId::new(Order::Tooltip, Id::new("loupe")));
+        for dy in -LOUPE_RADIUS..=LOUPE_RADIUS {
+            for dx in -LOUPE_RADIUS..=LOUPE_RADIUS {
+                let (sample_x, sample_y) = (x + dx, y + dy);
+                let cell_color = if sample_x < 0 || sample_y < 0 || sample_x >= real_image_size[0] as i64 || sample_y >= real_image_size[1] as i64 {
+                    Color32::BLACK
                 }
+                else {
+                    let sample = snapshot.get_pixel(sample_x as u32, sample_y as u32).0;
+                    Color32::from_rgb(sample[0], sample[1], sample[2])
+                };
 
-                let cropped_image = self.snapshot.as_ref().unwrap().crop_imm(crop_start_pos[0] as u32, crop_start_pos[1] as u32, width as u32, height as u32);
-                self.snapshot = Some(cropped_image.clone());
-                self.snapshots_undo.push_back(cropped_image);
-                self.snapshots_redo.clear();
+                let cell_min = loupe_min + Vec2::new((dx + LOUPE_RADIUS) as f32, (dy + LOUPE_RADIUS) as f32) * LOUPE_CELL_SIZE;
+                painter.rect_filled(Rect::from_min_size(cell_min, Vec2::splat(LOUPE_CELL_SIZE)), 0., cell_color);
             }
-            
-        }
-        else{
-            self.tooling = false;
         }
-        
-        match self.tool {
-            Tool::None => {}
-            _ => {
-                image_response.on_hover_cursor(CursorIcon::Crosshair);
+        let loupe_rect = Rect::from_min_size(loupe_min, Vec2::splat(loupe_size));
+        let center_rect = Rect::from_center_size(loupe_rect.center(), Vec2::splat(LOUPE_CELL_SIZE));
+        painter.rect_stroke(center_rect, 0., Stroke::new(1., Color32::WHITE));
+        painter.rect_stroke(loupe_rect, 0., Stroke::new(1., Color32::GRAY));
+
+        let text = format_color(color, self.color_format);
+        let label_rect = Rect::from_min_size(loupe_rect.left_bottom(), Vec2::new(loupe_size, 24.));
+        painter.rect_filled(label_rect, 0., Color32::from_black_alpha(200));
+        painter.text(label_rect.center(), Align2::CENTER_CENTER, &text, FontId::monospace(12.), Color32::WHITE);
+
+        if image_response.clicked() {
+            self.pen_color = srgb_to_linear(color);
+            self.picked_color = Some(color);
+            if let Ok(mut clipboard) = Clipboard::new() {
+                clipboard.set_text(text).ok();
             }
-        };
+        }
     }
 
+    fn update_step(&mut self, ui: &mut Ui, image_response: &Response, real_image_size: [usize; 2]) {
+        let rgb_color = linear_to_srgb(self.pen_color);
+        let color = [rgb_color[0], rgb_color[1], rgb_color[2], 255];
+        let view = ViewTransform::from_rect(image_response.rect, real_image_size);
 
+        if image_response.clicked() {
+            if let Some(pos) = image_response.interact_pointer_pos() {
+                let image_pos = get_real_image_pos(pos, &view);
 
+                self.document.as_mut().unwrap().add_step_marker(image_pos.into(), self.step_size, self.fonts.clone(), self.font_family.clone(), color);
+            }
+        }
 
+        // Outline of the marker that a click would place
+        if let Some(pos) = image_response.hover_pos() {
+            let stroke = Stroke::new(1.5, Color32::from_rgb(color[0], color[1], color[2]));
+            ui.painter().circle_stroke(pos, self.step_size * view.scale, stroke);
+        }
+    }
 
+    fn update_text(&mut self, ui: &mut Ui, image_response: &Response, real_image_size: [usize; 2]) {
+        let view = ViewTransform::from_rect(image_response.rect, real_image_size);
+        let scale = view.scale;
 
-    
-    fn render_top_panel(&mut self, ctx: &Context, frame: &mut Frame) {
-        if !self.show_settings && !self.show_credits {
-            TopBottomPanel::top("top panel")
-            .exact_height(36.)
-            .show(ctx, |ui| {
-                ui.horizontal(|ui| {
-                    ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
-                        let mut top_left_rect = ui.max_rect();
-                        top_left_rect.max.y = top_left_rect.min.y + 36.;
-                        top_left_rect.max.x = top_left_rect.max.x/2.;
-                        top_left_rect = top_left_rect.shrink(4.0);
-                        let mut top_left_panel_ui = ui.child_ui(top_left_rect, *ui.layout());
-                        
-                        
-                        top_left_panel_ui.horizontal(|ui| {
-
-                            let snapshot_button = ui.add(Button::new("📷 Snapshot"));
-                            if snapshot_button.clicked() {
-                                frame.set_visible(false);
-                                self.get_snapshot(ctx);
-                            }
-
-                            ComboBox::from_id_source(1)
-                            .selected_text(format!("🕓 {} sec", self.timer.unwrap()))
-                            .show_ui(ui, |ui| {
-                                let timer_values = [0., 1., 2., 3., 5., 10.]; 
-                                for timer_val in timer_values{
-                                    ui.selectable_value(
-                                        &mut self.timer,
-                                        Some(timer_val),
-                                        format!("🕓 {} sec", timer_val)
-                                    );
-                                }
-                            });
+        if image_response.clicked() {
+            self.commit_text();
 
-                            ComboBox::from_id_source(0)
-                            .selected_text(format!("🖵 Display {}", self.display.unwrap()))
-                            .show_ui(ui, |ui| {
-                                for (i, display) in DisplayInfo::all().unwrap().iter().enumerate(){
-                                    ui.selectable_value(
-                                        &mut self.display,
-                                        Some(i),
-                                        format!("🖵 Display {}  {}x{}", i, display.width as f32 * display.scale_factor, display.height as f32 * display.scale_factor)
-                                    );
-                                }
-                            });
+            if let Some(pos) = image_response.interact_pointer_pos() {
+                self.text_box = Some(TextBox::new(get_real_image_pos(pos, &view)));
+            }
+        }
 
-                            if self.snapshot.is_some(){
-                                let save_button = ui.add(Button::new("💾 Save as"));
-                                if save_button.clicked() {
-                                    self.save_snapshot();
-                                }
-                            }
+        let (color, background) = self.get_text_colors();
+        let mut commit = false;
+        let mut cancel = false;
 
-                            if self.snapshot.is_some(){
-                                let copy_button = ui.add(Button::new("📄 Copy"));
-                                if copy_button.clicked() {
-                                    self.copy_snapshot();
-                                }
-                            }
+        if let Some(text_box) = self.text_box.as_mut() {
+            let (width, height) = get_text_size(&text_box.text, &self.fonts, self.text_size);
+            let min = view.to_screen(text_box.pos);
+            let text_rect = Rect::from_min_size(min, Vec2::new(width, height) * scale);
 
-                            let settings_button = ui.add(Button::new("🔨 Settings"));
-                            if settings_button.clicked() {
+            match background {
+                Some(background) => {
+                    ui.painter().rect_filled(text_rect, 0., Color32::from_rgb(background[0], background[1], background[2]));
+                }
+                None => {
+                    let stroke = Stroke::new(1., Color32::from_rgba_premultiplied(255, 255, 255, 150));
+                    ui.painter().add(Shape::dashed_line(&[text_rect.left_top(), text_rect.right_top(), text_rect.right_bottom(), text_rect.left_bottom(), text_rect.left_top()], stroke, 6., 6.));
+                }
+            }
 
-                                for hotkey in self.hotkeys.iter_mut(){
-                                    hotkey.tmp_code = hotkey.code.clone();
-                                    hotkey.tmp_modifier = hotkey.modifier.clone();
-                                }
+            // Leave room for the next character while typing
+            let edit_rect = Rect::from_min_size(min, Vec2::new(width + self.text_size * 2., height) * scale);
+            let text_edit = TextEdit::singleline(&mut text_box.text)
+                .font(FontId::new(self.text_size * scale, self.font_family.clone()))
+                .text_color(Color32::from_rgb(color[0], color[1], color[2]))
+                .frame(false)
+                .margin(Vec2::splat(self.text_size * 0.2 * scale))
+                .desired_width(f32::INFINITY);
+            let text_response = ui.put(edit_rect, text_edit);
+
+            if !text_box.focused {
+                text_response.request_focus();
+                text_box.focused = true;
+            }
 
-                                self.show_tools = false;
-                                self.show_settings = true;
-                            }
+            if text_response.lost_focus() {
+                if ui.input(|i| i.key_pressed(Key::Escape)) {
+                    cancel = true;
+                }
+                else {
+                    commit = true;
+                }
+            }
+        }
 
-                            let credits_button = ui.add(Button::new("💻 Credits"));
-                            if credits_button.clicked() {
-                                self.show_tools = false;
-                                self.show_credits = true;
-                            }
+        if commit {
+            self.commit_text();
+        }
+        else if cancel {
+            self.text_box = None;
+        }
+    }
 
-                        });
+    // Current drag position, snapped to 15° steps while Shift is held
+    fn get_arrow_end(&self, ui: &Ui) -> Pos2 {
+        if ui.input(|i| i.modifiers.shift) {
+            return snap_angle(self.last_pos, self.current_pos, 15.);
+        }
+        return self.current_pos;
+    }
 
-                        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                            let mut top_right_rect = ui.max_rect();
-                            top_right_rect.max.y = top_right_rect.min.y + 36.;
-                            top_right_rect.min.x = top_right_rect.max.x/2.;
-                            top_right_rect = top_right_rect.shrink(4.0);
-                            let mut top_right_panel_ui = ui.child_ui(top_right_rect, *ui.layout());
-    
-                            top_right_panel_ui.horizontal(|ui| {
-                                if !self.snapshots_undo.is_empty(){
-                                    let tool_toggle_button = ui.add(Button::new("🔧 Show tools"));
-                                    if tool_toggle_button.clicked() {
-                                        self.show_tools = !self.show_tools;
-                                    }
-                                }
-                            });
-                        });
-                        
-                    });
+    // The annotation that the current drag of the pen, highlighter, eraser or a shape tool would add
+    fn get_drag_annotation(&self, ui: &Ui) -> Option<Annotation> {
+        let rgb_color = linear_to_srgb(self.pen_color);
+        let color = [rgb_color[0], rgb_color[1], rgb_color[2], 255];
 
-                    
-                });
-            });
+        match self.tool {
+            Tool::Pen => {
+                let smoothing = if self.pen_smoothing { PEN_SMOOTHING } else { 0. };
+                Some(Annotation::Stroke {
+                    points: smooth_points(&self.stroke_points, smoothing),
+                    color: color,
+                    width: (self.pen_size * 2 + 1) as f32
+                })
+            }
+            Tool::Highlighter => {
+                let rgb_color = linear_to_srgb(self.highlighter_color);
+                Some(Annotation::Stroke {
+                    points: self.stroke_points.clone(),
+                    color: [rgb_color[0], rgb_color[1], rgb_color[2], HIGHLIGHTER_ALPHA],
+                    width: (self.highlighter_size * 2 + 1) as f32
+                })
+            }
+            Tool::Eraser => Some(Annotation::Erase {
+                points: self.stroke_points.clone(),
+                width: (self.eraser_size * 2 + 1) as f32
+            }),
+            Tool::Rectangle => Some(Annotation::Rectangle {
+                start: self.last_pos.into(),
+                end: self.current_pos.into(),
+                color: color,
+                size: self.pen_size as i32,
+                filled: self.shape_filled,
+                angle: 0.
+            }),
+            Tool::Ellipse => Some(Annotation::Ellipse {
+                start: self.last_pos.into(),
+                end: self.current_pos.into(),
+                color: color,
+                size: self.pen_size as i32,
+                filled: self.shape_filled,
+                angle: 0.
+            }),
+            Tool::Arrow => Some(Annotation::Arrow {
+                start: self.last_pos.into(),
+                end: self.get_arrow_end(ui).into(),
+                color: color,
+                size: self.pen_size as i32,
+                heads: self.arrow_heads,
+                style: self.arrow_style
+            }),
+            _ => None,
         }
     }
 
-    fn render_side_panel(&mut self, ctx: &Context, _frame: &mut Frame) {
-        if self.show_tools{
-            SidePanel::right("right panel")
-            .exact_width(80.)
-            .show(ctx, |ui| {
-                let mut side_rect = ui.max_rect();
-                side_rect.max.x = side_rect.max.x - 12.;
-                side_rect = side_rect.shrink(4.0);
-                let mut side_panel_ui = ui.child_ui(side_rect, *ui.layout());
+    fn get_style(&self) -> AnnotationStyle {
+        AnnotationStyle {
+            pen_color: self.pen_color,
+            pen_size: self.pen_size,
+            shape_filled: self.shape_filled,
+            arrow_heads: self.arrow_heads,
+            arrow_style: self.arrow_style,
+            highlighter_color: self.highlighter_color,
+            highlighter_size: self.highlighter_size,
+            eraser_size: self.eraser_size,
+            step_size: self.step_size,
+            text_size: self.text_size,
+            text_background: self.text_background,
+            text_background_color: self.text_background_color,
+            font_family: self.font_family.clone(),
+        }
+    }
 
+    // Shows the style of the annotation in the side panel
+    fn load_style(&mut self, annotation: &Annotation) {
+        let to_linear = |color: &[u8; 4]| srgb_to_linear([color[0], color[1], color[2]]);
+        let to_size = |width: f32| ((width - 1.) / 2.).round().max(1.) as usize;
 
-                side_panel_ui.vertical(|ui| {
-                    ui.with_layout(Layout::top_down(Align::Center), |ui| {
-                        let button_size = Vec2::from([40., 40.]);
-                        
-                        let none_button = ui.add(Button::new("🚫").rounding(5.).min_size(button_size));
-                        if none_button.clicked() {
-                            self.tool = Tool::None;
-                        }
-                        ui.separator();
+        match annotation {
+            Annotation::Stroke { color, width, .. } if color[3] == 255 => {
+                self.pen_color = to_linear(color);
+                self.pen_size = to_size(*width);
+            }
+            Annotation::Stroke { color, width, .. } => {
+                self.highlighter_color = to_linear(color);
+                self.highlighter_size = to_size(*width);
+            }
+            Annotation::Erase { width, .. } => self.eraser_size = to_size(*width),
+            Annotation::Rectangle { color, size, filled, .. } | Annotation::Ellipse { color, size, filled, .. } => {
+                self.pen_color = to_linear(color);
+                self.pen_size = *size as usize;
+                self.shape_filled = *filled;
+            }
+            Annotation::Arrow { color, size, heads, style, .. } => {
+                self.pen_color = to_linear(color);
+                self.pen_size = *size as usize;
+                self.arrow_heads = *heads;
+                self.arrow_style = *style;
+            }
+            Annotation::Text { fonts, family, size, color, background, .. } => {
+                self.pen_color = to_linear(color);
+                self.text_size = *size;
+                self.text_background = background.is_some();
+                if let Some(background) = background {
+                    self.text_background_color = to_linear(background);
+                }
+                self.fonts = fonts.clone();
+                self.font_family = family.clone();
+                self.font_name = match family {
+                    FontFamily::Name(path) => Path::new(path.as_ref()).file_name().unwrap().to_string_lossy().to_string(),
+                    _ => "Default".to_string(),
+                };
+            }
+            Annotation::Step { radius, color, .. } => {
+                self.pen_color = to_linear(color);
+                self.step_size = *radius;
+            }
+        }
+    }
 
-                        let pen_button = ui.add(Button::new("✏").rounding(5.).min_size(button_size));
-                        if pen_button.clicked() {
-                            self.tool = Tool::Pen;
-                        }
-                        ui.separator();
+    // Changes the annotation to the style in the side panel
+    fn apply_style(&self, annotation: &mut Annotation) {
+        let rgb_color = linear_to_srgb(self.pen_color);
+        let pen_color = [rgb_color[0], rgb_color[1], rgb_color[2], 255];
 
-                        let crop_button = ui.add(Button::new("✂").rounding(5.).min_size(button_size));
-                        if crop_button.clicked() {
-                            self.tool = Tool::Crop;
-                        }
-                        ui.separator();
+        match annotation {
+            Annotation::Stroke { color, width, .. } if color[3] == 255 => {
+                *color = pen_color;
+                *width = (self.pen_size * 2 + 1) as f32;
+            }
+            Annotation::Stroke { color, width, .. } => {
+                let rgb_color = linear_to_srgb(self.highlighter_color);
+                *color = [rgb_color[0], rgb_color[1], rgb_color[2], HIGHLIGHTER_ALPHA];
+                *width = (self.highlighter_size * 2 + 1) as f32;
+            }
+            Annotation::Erase { width, .. } => *width = (self.eraser_size * 2 + 1) as f32,
+            Annotation::Rectangle { color, size, filled, .. } | Annotation::Ellipse { color, size, filled, .. } => {
+                *color = pen_color;
+                *size = self.pen_size as i32;
+                *filled = self.shape_filled;
+            }
+            Annotation::Arrow { color, size, heads, style, .. } => {
+                *color = pen_color;
+                *size = self.pen_size as i32;
+                *heads = self.arrow_heads;
+                *style = self.arrow_style;
+            }
+            Annotation::Text { fonts, family, size, color, background, .. } => {
+                let (text_color, text_background) = self.get_text_colors();
+                *fonts = self.fonts.clone();
+                *family = self.font_family.clone();
+                *size = self.text_size;
+                *color = text_color;
+                *background = text_background;
+            }
+            Annotation::Step { radius, color, .. } => {
+                *radius = self.step_size;
+                *color = pen_color;
+            }
+        }
+    }
+
+    // Called when a side panel setting changes
+    fn restyle_selection(&mut self, ctx: &Context) {
+        let index = match self.selection {
+            Some(index) if self.tool == Tool::Select => index,
+            _ => return,
+        };
+
+        let mut annotation = self.document.as_ref().unwrap().annotations[index].clone();
+        self.apply_style(&mut annotation);
+
+        let document = self.document.as_mut().unwrap();
+        if !(self.restyling && document.amend(index, annotation.clone())) {
+            document.replace(index, annotation);
+        }
+        self.restyling = ctx.input(|i| i.pointer.any_down());
+    }
+
+    // The tool whose settings the side panel shows, for a selection it is the tool that drew it
+    fn get_settings_tool(&self) -> Tool {
+        if self.tool != Tool::Select {
+            return self.tool;
+        }
+
+        match self.selection.and_then(|index| self.document.as_ref()?.annotations.get(index)) {
+            Some(Annotation::Stroke { color, .. }) if color[3] == 255 => Tool::Pen,
+            Some(Annotation::Stroke { .. }) => Tool::Highlighter,
+            Some(Annotation::Erase { .. }) => Tool::Eraser,
+            Some(Annotation::Rectangle { .. }) => Tool::Rectangle,
+            Some(Annotation::Ellipse { .. }) => Tool::Ellipse,
+            Some(Annotation::Arrow { .. }) => Tool::Arrow,
+            Some(Annotation::Text { .. }) => Tool::Text,
+            Some(Annotation::Step { .. }) => Tool::Step,
+            None => Tool::Select,
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        if self.selection != Some(index) {
+            self.selection = Some(index);
+            let annotation = self.document.as_ref().unwrap().annotations[index].clone();
+            self.load_style(&annotation);
+        }
+    }
+
+    // Resize handles on the corners and sides of the annotation bounds and the rotation handle
+    // above the top side, on screen
+    fn get_selection_handles(&self, annotation: &Annotation, view: &ViewTransform) -> Vec<(SelectionHandle, Pos2)> {
+        let bounds = annotation.bounds();
+        let center = bounds.center();
+        let to_screen = |point: Pos2| view.to_screen(rotate_point(point.into(), center.into(), annotation.angle()).into());
+
+        let mut handles = Vec::new();
+        for y in -1..=1 {
+            for x in -1..=1 {
+                if x != 0 || y != 0 {
+                    let point = center + Vec2::new(x as f32, y as f32) * bounds.size() / 2.;
+                    handles.push((SelectionHandle::Resize(x, y), to_screen(point)));
+                }
+            }
+        }
+        if annotation.can_rotate() {
+            let top = to_screen(Pos2::new(center.x, bounds.min.y));
+            handles.push((SelectionHandle::Rotate, top + Vec2::angled(annotation.angle() - std::f32::consts::FRAC_PI_2) * 24.));
+        }
+
+        return handles;
+    }
+
+    // The annotation and its part under pos, the handles of the selection come first
+    fn get_selection_target(&self, pos: Pos2, view: &ViewTransform) -> Option<(usize, SelectionHandle)> {
+        let annotations = &self.document.as_ref().unwrap().annotations;
+        let image_pos = get_real_image_pos(pos, view);
+
+        if let Some(index) = self.selection {
+            for (handle, handle_pos) in self.get_selection_handles(&annotations[index], view) {
+                if handle_pos.distance(pos) <= SELECTION_TOLERANCE {
+                    return Some((index, handle));
+                }
+            }
+        }
+
+        // The last annotation is drawn on top
+        return annotations.iter()
+            .rposition(|annotation| annotation.hit_test(image_pos.into(), SELECTION_TOLERANCE / view.scale))
+            .map(|index| (index, SelectionHandle::Move));
+    }
+
+    fn update_select(&mut self, ui: &mut Ui, image_response: &Response, real_image_size: [usize; 2]) {
+        let view = ViewTransform::from_rect(image_response.rect, real_image_size);
+        let to_image = |pos: Pos2| get_real_image_pos(pos, &view);
+
+        if self.selection.map_or(false, |index| index >= self.document.as_ref().unwrap().annotations.len()) {
+            self.selection = None;
+        }
+
+        let hovered = image_response.hover_pos().and_then(|pos| self.get_selection_target(pos, &view));
+
+        if image_response.drag_started() {
+            // The drag starts a bit after the press, pick what was under the press
+            if let Some(origin) = ui.input(|i| i.pointer.press_origin()) {
+                if let Some((index, handle)) = self.get_selection_target(origin, &view) {
+                    self.select(index);
+                    let annotation = self.document.as_ref().unwrap().annotations[index].clone();
+                    self.selection_drag = Some((handle, annotation, to_image(origin)));
+                }
+            }
+        }
+        else if image_response.clicked() {
+            match hovered {
+                Some((index, _)) => self.select(index),
+                None => self.selection = None,
+            }
+        }
 
-                        let undo_button = ui.add(Button::new("↩").rounding(5.).min_size(button_size));
-                        if undo_button.clicked() {
-                            self.undo();
+        if image_response.dragged() {
+            if let (Some(index), Some((handle, original, start)), Some(pos)) = (self.selection, self.selection_drag.as_ref(), image_response.hover_pos()) {
+                let current = to_image(pos);
+                let bounds = original.bounds();
+                let center = bounds.center();
+                let mut annotation = original.clone();
+
+                match handle {
+                    SelectionHandle::Move => annotation.translate((current - *start).into()),
+                    SelectionHandle::Resize(x, y) => {
+                        // Move the dragged sides in the frame of the turned annotation
+                        let local: Pos2 = rotate_point(current.into(), center.into(), -original.angle()).into();
+                        let mut min = bounds.min;
+                        let mut max = bounds.max;
+                        match x {
+                            -1 => min.x = local.x,
+                            1 => max.x = local.x,
+                            _ => {}
+                        }
+                        match y {
+                            -1 => min.y = local.y,
+                            1 => max.y = local.y,
+                            _ => {}
                         }
+
+                        // The annotation turns around the center of its new bounds, move them so
+                        // that the opposite side stays in place
+                        let resized = Rect::from_two_pos(min, max);
+                        let resized_center: Pos2 = rotate_point(resized.center().into(), center.into(), original.angle()).into();
+                        annotation.resize(bounds, resized.translate(resized_center - resized.center()));
+                    }
+                    SelectionHandle::Rotate => {
+                        let mut angle = (current - center).angle() - (*start - center).angle();
+                        if ui.input(|i| i.modifiers.shift) {
+                            let step = 15_f32.to_radians();
+                            angle = ((original.angle() + angle) / step).round() * step - original.angle();
+                        }
+                        annotation.rotate(center.into(), angle);
+                    }
+                }
+
+                self.document.as_mut().unwrap().annotations[index] = annotation;
+            }
+        }
+        else if image_response.drag_released() {
+            if let (Some(index), Some((_, original, _))) = (self.selection, self.selection_drag.take()) {
+                // Put the annotation back as it was, so the whole drag is one undo step
+                let document = self.document.as_mut().unwrap();
+                let annotation = std::mem::replace(&mut document.annotations[index], original);
+                document.replace(index, annotation);
+            }
+        }
+
+        // Keys only act on the selection while no text field has the focus
+        if let Some(index) = self.selection {
+            if self.selection_drag.is_none() && ui.memory(|m| m.focus().is_none()) {
+                let (delete, deselect, nudge) = ui.input(|i| {
+                    let step = if i.modifiers.shift { 10. } else { 1. };
+                    let mut nudge = (0., 0.);
+                    if i.key_pressed(Key::ArrowLeft) { nudge.0 -= step; }
+                    if i.key_pressed(Key::ArrowRight) { nudge.0 += step; }
+                    if i.key_pressed(Key::ArrowUp) { nudge.1 -= step; }
+                    if i.key_pressed(Key::ArrowDown) { nudge.1 += step; }
+                    (i.key_pressed(Key::Delete) || i.key_pressed(Key::Backspace), i.key_pressed(Key::Escape), nudge)
+                });
+
+                let document = self.document.as_mut().unwrap();
+                if delete {
+                    document.remove(index);
+                    self.selection = None;
+                }
+                else if deselect {
+                    self.selection = None;
+                }
+                else if nudge != (0., 0.) {
+                    let mut annotation = document.annotations[index].clone();
+                    annotation.translate(nudge);
+                    document.replace(index, annotation);
+                }
+            }
+        }
+
+        if let Some(index) = self.selection {
+            let annotation = &self.document.as_ref().unwrap().annotations[index];
+            let handles = self.get_selection_handles(annotation, &view);
+            let corner = |x, y| handles.iter().find(|(handle, _)| *handle == SelectionHandle::Resize(x, y)).unwrap().1;
+
+            let stroke = Stroke::new(1., Color32::from_rgba_premultiplied(255, 255, 255, 150));
+            ui.painter().add(Shape::closed_line(vec![corner(-1, -1), corner(1, -1), corner(1, 1), corner(-1, 1)], stroke));
+            for (handle, pos) in handles.iter() {
+                match handle {
+                    SelectionHandle::Rotate => {
+                        ui.painter().line_segment([corner(0, -1), *pos], stroke);
+                        ui.painter().circle(*pos, 4., Color32::WHITE, Stroke::new(1., Color32::DARK_GRAY));
+                    }
+                    _ => {
+                        ui.painter().rect(Rect::from_center_size(*pos, Vec2::splat(7.)), 0., Color32::WHITE, Stroke::new(1., Color32::DARK_GRAY));
+                    }
+                }
+            }
+        }
+
+        let handle = match self.selection_drag.as_ref() {
+            Some((handle, _, _)) => Some(*handle),
+            None => hovered.map(|(_, handle)| handle),
+        };
+        if image_response.hovered() || self.selection_drag.is_some() {
+            ui.ctx().set_cursor_icon(handle.map_or(CursorIcon::Default, get_handle_cursor));
+        }
+    }
+
+    fn apply_crop(&mut self) {
+        if let Some(rect) = self.crop_rect.take() {
+            let (x, y) = (rect.min.x.round(), rect.min.y.round());
+            let width = rect.max.x.round() - x;
+            let height = rect.max.y.round() - y;
+            if width >= 1. && height >= 1. {
+                self.document.as_mut().unwrap().crop(x as i64, y as i64, width as u32, height as u32);
+            }
+        }
+    }
+
+    fn update_crop(&mut self, ui: &mut Ui, image_response: &Response, real_image_size: [usize; 2]) {
+        let image_rect = image_response.rect;
+        let view = ViewTransform::from_rect(image_rect, real_image_size);
+        let image_size = Vec2::new(real_image_size[0] as f32, real_image_size[1] as f32);
+        let to_image = |pos: Pos2| get_real_image_pos(pos, &view);
+        let to_screen = |pos: Pos2| view.to_screen(pos);
+
+        // Handles on the corners and sides of the crop rectangle, on screen
+        let get_handles = |crop_rect: Rect| {
+            let mut handles = Vec::new();
+            for y in -1..=1 {
+                for x in -1..=1 {
+                    if x != 0 || y != 0 {
+                        handles.push((SelectionHandle::Resize(x, y), to_screen(crop_rect.center() + Vec2::new(x as f32, y as f32) * crop_rect.size() / 2.)));
+                    }
+                }
+            }
+            handles
+        };
+        let get_handle_at = |crop_rect: Option<Rect>, pos: Pos2| {
+            let crop_rect = crop_rect?;
+            for (handle, handle_pos) in get_handles(crop_rect) {
+                if handle_pos.distance(pos) <= SELECTION_TOLERANCE {
+                    return Some(handle);
+                }
+            }
+            if crop_rect.contains(to_image(pos)) {
+                return Some(SelectionHandle::Move);
+            }
+            None
+        };
+
+        if image_response.drag_started() {
+            if let Some(origin) = ui.input(|i| i.pointer.press_origin()) {
+                let start = to_image(origin).clamp(Pos2::ZERO, image_size.to_pos2());
+                self.crop_drag = match get_handle_at(self.crop_rect, origin) {
+                    Some(handle) => Some((handle, self.crop_rect.unwrap(), start)),
+                    // Outside of the rectangle a new one is drawn from the press
+                    None => Some((SelectionHandle::Resize(1, 1), Rect::from_min_max(start, start), start)),
+                };
+            }
+        }
+
+        if image_response.dragged() {
+            if let (Some((handle, original, start)), Some(pos)) = (self.crop_drag, image_response.hover_pos()) {
+                let current = to_image(pos);
+                self.crop_rect = Some(match handle {
+                    SelectionHandle::Resize(x, y) => resize_crop_rect(original, (x, y), current, self.crop_aspect.value(), image_size),
+                    _ => {
+                        let min = (original.min + (current - start)).clamp(Pos2::ZERO, (image_size - original.size()).max(Vec2::ZERO).to_pos2());
+                        Rect::from_min_size(min, original.size())
+                    }
+                });
+            }
+        }
+        else if image_response.drag_released() {
+            self.crop_drag = None;
+            if self.crop_rect.map_or(false, |rect| rect.width() < 1. || rect.height() < 1.) {
+                self.crop_rect = None;
+            }
+        }
+
+        if self.crop_rect.is_some() && ui.memory(|m| m.focus().is_none()) {
+            if ui.input(|i| i.key_pressed(Key::Enter)) {
+                self.apply_crop();
+            }
+            else if ui.input(|i| i.key_pressed(Key::Escape)) {
+                self.crop_rect = None;
+            }
+        }
+
+        if let Some(crop_rect) = self.crop_rect {
+            let rect = Rect::from_min_max(to_screen(crop_rect.min), to_screen(crop_rect.max));
+            let painter = ui.painter();
+            dim_outside(painter, image_rect, rect);
+
+            let stroke = Stroke::new(1., Color32::from_rgba_premultiplied(255, 255, 255, 150));
+            painter.add(Shape::dashed_line(&[rect.left_top(), rect.right_top(), rect.right_bottom(), rect.left_bottom(), rect.left_top()], stroke, 6., 6.));
+            for (_, pos) in get_handles(crop_rect) {
+                painter.rect(Rect::from_center_size(pos, Vec2::splat(7.)), 0., Color32::WHITE, Stroke::new(1., Color32::DARK_GRAY));
+            }
+
+            let label = format!("{} x {}", (crop_rect.max.x.round() - crop_rect.min.x.round()) as u32, (crop_rect.max.y.round() - crop_rect.min.y.round()) as u32);
+            let label_pos = Pos2::new(rect.left(), rect.top().max(image_rect.top().max(painter.clip_rect().top()) + 20.) - 6.);
+            painter.text(label_pos, Align2::LEFT_BOTTOM, label, FontId::monospace(14.), Color32::WHITE);
+        }
+
+        let handle = match self.crop_drag {
+            Some((handle, _, _)) => Some(handle),
+            None => image_response.hover_pos().and_then(|pos| get_handle_at(self.crop_rect, pos)),
+        };
+        if image_response.hovered() || self.crop_drag.is_some() {
+            ui.ctx().set_cursor_icon(handle.map_or(CursorIcon::Crosshair, get_handle_cursor));
+        }
+    }
+
+    fn update_editing(&mut self, ui: &mut Ui, image_response: Response, real_image_size: [usize; 2], texture_id: TextureId) {
+        if self.tool == Tool::Text {
+            self.update_text(ui, &image_response, real_image_size);
+            image_response.on_hover_cursor(CursorIcon::Text);
+            return;
+        }
+
+        if self.tool == Tool::Step {
+            self.update_step(ui, &image_response, real_image_size);
+            image_response.on_hover_cursor(CursorIcon::Crosshair);
+            return;
+        }
+
+        if self.tool == Tool::ColorPicker {
+            self.update_color_picker(ui, &image_response, real_image_size);
+            image_response.on_hover_cursor(CursorIcon::Crosshair);
+            return;
+        }
+
+        if self.tool == Tool::Select {
+            self.update_select(ui, &image_response, real_image_size);
+            return;
+        }
+
+        if self.tool == Tool::Crop {
+            self.update_crop(ui, &image_response, real_image_size);
+            return;
+        }
+
+        let view = ViewTransform::from_rect(image_response.rect, real_image_size);
+
+        if image_response.dragged(){
+            // Update last and current position
+            if !self.tooling {
+                self.last_pos = match image_response.hover_pos() {
+                    Some(pos) => get_real_image_pos(pos, &view),
+                    None => self.last_pos,
+                };
+                self.tooling = true;
+                self.stroke_points = vec![self.last_pos.into()];
+            }
+
+            self.current_pos = match image_response.hover_pos() {
+                Some(pos) => get_real_image_pos(pos, &view),
+                None => self.current_pos,
+            };
+
+            // Apply tool
+            if self.tool == Tool::Pen || self.tool == Tool::Highlighter || self.tool == Tool::Eraser {
+                let image_current_pos: (f32, f32) = self.current_pos.into();
+                if self.stroke_points.last() != Some(&image_current_pos) {
+                    self.stroke_points.push(image_current_pos);
+                }
+            }
+
+            if self.tool == Tool::Redact {
+                let rect = Rect::from_two_pos(view.to_screen(self.last_pos), view.to_screen(self.current_pos));
+                ui.painter().rect(rect, 0., Color32::from_black_alpha(120), Stroke::new(1., Color32::from_rgba_premultiplied(255, 255, 255, 150)));
+            }
+            else if let Some(annotation) = self.get_drag_annotation(ui) {
+                annotation.paint(&ui.painter().with_clip_rect(image_response.rect), image_response.rect, real_image_size, texture_id);
+            }
+
+        } else if image_response.drag_released() {  
+            if let Some(annotation) = self.get_drag_annotation(ui) {
+                self.document.as_mut().unwrap().add(annotation);
+            }
+            else if self.tool == Tool::Redact {
+                let (x, y, width, height) = get_region_rect(view.to_screen(self.last_pos), view.to_screen(self.current_pos), &view, real_image_size);
+
+                let rgb_color = linear_to_srgb(self.redact_color);
+                let color = [rgb_color[0], rgb_color[1], rgb_color[2], 255];
+
+                self.document.as_mut().unwrap().redact(x, y, width, height, self.redact_style, self.redact_strength, color);
+            }
+        }
+        else{
+            self.tooling = false;
+        }
+        
+        match self.tool {
+            Tool::None => {}
+            _ => {
+                image_response.on_hover_cursor(CursorIcon::Crosshair);
+            }
+        };
+    }
+
+
+
+
+
+
+    
+    fn render_region_overlay(&mut self, ctx: &Context, frame: &mut Frame) {
+        let mut selected_region: Option<DynamicImage> = None;
+        let cancelled = ctx.input(|i| i.key_pressed(Key::Escape));
+
+        CentralPanel::default()
+        .frame(egui::Frame::none())
+        .show(ctx, |ui| {
+            let region_selection = self.region_selection.as_mut().unwrap();
+            let color_image = ColorImage::from_rgba_unmultiplied(
+                [region_selection.frozen.width() as usize, region_selection.frozen.height() as usize],
+                region_selection.frozen.as_bytes(),
+            );
+
+            let texture_handle = ui.ctx().load_texture(
+                "region",
+                color_image,
+                Default::default(),
+            );
+
+            let image_rect = ui.max_rect();
+            let mut image_ui = ui.child_ui(image_rect, *ui.layout());
+            let image_response = image_ui.add(ImageButton::new(texture_handle.id(), image_rect.size()).frame(false).sense(Sense::click_and_drag()));
+            let view = ViewTransform::from_rect(image_response.rect, texture_handle.size());
+
+            if image_response.drag_started() {
+                region_selection.start = image_response.hover_pos();
+                region_selection.end = region_selection.start;
+            }
+            else if image_response.dragged() {
+                region_selection.end = match image_response.hover_pos() {
+                    Some(pos) => Some(pos),
+                    None => region_selection.end,
+                };
+            }
+
+            let painter = ui.painter();
+            let full_rect = image_response.rect;
+
+            match (region_selection.start, region_selection.end) {
+                (Some(start), Some(end)) => {
+                    let rect = Rect::from_two_pos(start, end);
+
+                    dim_outside(painter, full_rect, rect);
+
+                    let stroke = Stroke::new(1., Color32::from_rgba_premultiplied(255, 255, 255, 150));
+                    painter.add(Shape::dashed_line(&[rect.left_top(), rect.right_top(), rect.right_bottom(), rect.left_bottom(), rect.left_top()], stroke, 6., 6.));
+
+                    let (_, _, width, height) = get_region_rect(start, end, &view, texture_handle.size());
+                    let label_pos = Pos2::new(rect.left(), rect.top().max(full_rect.top() + 20.) - 4.);
+                    painter.text(label_pos, Align2::LEFT_BOTTOM, format!("{} x {}", width, height), FontId::monospace(14.), Color32::WHITE);
+                }
+                _ => {
+                    painter.rect_filled(full_rect, 0., Color32::from_rgba_premultiplied(0, 0, 0, 120));
+                    painter.text(full_rect.center(), Align2::CENTER_CENTER, "Drag to select a region, Esc to cancel", FontId::proportional(18.), Color32::WHITE);
+                }
+            }
+
+            if image_response.drag_released() {
+                if let (Some(start), Some(end)) = (region_selection.start, region_selection.end) {
+                    let (x, y, width, height) = get_region_rect(start, end, &view, texture_handle.size());
+                    selected_region = crop_image(&region_selection.frozen, x as i64, y as i64, width, height);
+                    if selected_region.is_none() {
+                        region_selection.start = None;
+                        region_selection.end = None;
+                    }
+                }
+            }
+
+            image_response.on_hover_cursor(CursorIcon::Crosshair);
+        });
+
+        if let Some(region) = selected_region {
+            self.tx.send(Ok(region)).ok();
+            self.end_region_selection(frame);
+            ctx.request_repaint();
+        }
+        else if cancelled {
+            self.end_region_selection(frame);
+        }
+    }
+
+    fn render_countdown(&mut self, ctx: &Context, frame: &mut Frame) {
+        let remaining = self.countdown.unwrap().saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            self.end_countdown(frame);
+            frame.set_visible(false);
+            self.get_snapshot(ctx);
+            return;
+        }
+
+        let mut cancelled = ctx.input(|i| i.key_pressed(Key::Escape));
+
+        CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.label(RichText::new(format!("🕓 {}", remaining.as_secs_f64().ceil())).size(32.));
+                ui.add_space(5.);
+
+                let cancel_button = ui.add(Button::new("Cancel"));
+                if cancel_button.clicked() {
+                    cancelled = true;
+                }
+            });
+        });
+
+        if cancelled {
+            self.end_countdown(frame);
+        }
+        else {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+    }
+
+    fn render_top_panel(&mut self, ctx: &Context, frame: &mut Frame) {
+        if !self.show_settings && !self.show_credits {
+            TopBottomPanel::top("top panel")
+            .exact_height(36.)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
+                        let mut top_left_rect = ui.max_rect();
+                        top_left_rect.max.y = top_left_rect.min.y + 36.;
+                        top_left_rect.max.x = top_left_rect.max.x/2.;
+                        top_left_rect = top_left_rect.shrink(4.0);
+                        let mut top_left_panel_ui = ui.child_ui(top_left_rect, *ui.layout());
+                        
+                        
+                        top_left_panel_ui.horizontal(|ui| {
+
+                            let snapshot_enabled = self.capture_mode != CaptureMode::Window || self.window.is_some();
+                            let snapshot_button = ui.add_enabled(snapshot_enabled, Button::new("📷 Snapshot"));
+                            if snapshot_button.clicked() {
+                                self.start_snapshot(ctx, frame);
+                            }
+
+                            ComboBox::from_id_source("capture mode")
+                            .selected_text(self.capture_mode.label())
+                            .show_ui(ui, |ui| {
+                                for capture_mode in [CaptureMode::Display, CaptureMode::Region, CaptureMode::Window] {
+                                    ui.selectable_value(
+                                        &mut self.capture_mode,
+                                        capture_mode,
+                                        capture_mode.label()
+                                    );
+                                }
+                            });
+
+                            ComboBox::from_id_source(1)
+                            .selected_text(format!("🕓 {} sec", self.timer.unwrap()))
+                            .show_ui(ui, |ui| {
+                                let timer_values = [0., 1., 2., 3., 5., 10.]; 
+                                for timer_val in timer_values{
+                                    ui.selectable_value(
+                                        &mut self.timer,
+                                        Some(timer_val),
+                                        format!("🕓 {} sec", timer_val)
+                                    );
+                                }
+
+                                ui.horizontal(|ui| {
+                                    let mut custom_timer = self.timer.unwrap();
+                                    ui.label("🕓 Custom");
+                                    if ui.add(DragValue::new(&mut custom_timer).clamp_range(0..=3600).suffix(" sec")).changed() {
+                                        self.timer = Some(custom_timer);
+                                    }
+                                });
+                            });
+
+                            if self.capture_mode == CaptureMode::Window {
+                                let selected_window = match self.window.as_ref() {
+                                    Some(window) => format!("🗖 {}", window.title.chars().take(20).collect::<String>()),
+                                    None => "🗖 Select window".to_string(),
+                                };
+
+                                ComboBox::from_id_source("window")
+                                .selected_text(selected_window)
+                                .show_ui(ui, |ui| {
+                                    let windows = get_windows();
+                                    if windows.is_empty() {
+                                        ui.label("No windows found");
+                                    }
+                                    for window in windows {
+                                        let label = format!("🗖 {}  {}x{}", window.title, window.width, window.height);
+                                        ui.selectable_value(
+                                            &mut self.window,
+                                            Some(window),
+                                            label
+                                        );
+                                    }
+                                });
+
+                                ui.checkbox(&mut self.window_decorations, "Decorations");
+                            }
+                            else {
+                                let selected_display = match self.display {
+                                    Some(display) => format!("🖵 Display {}", display),
+                                    None => "🖵 All displays".to_string(),
+                                };
+
+                                ComboBox::from_id_source(0)
+                                .selected_text(selected_display)
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(
+                                        &mut self.display,
+                                        None,
+                                        "🖵 All displays"
+                                    );
+                                    match self.capture_source.list_displays() {
+                                        Ok(display_vec) => {
+                                            for (i, display) in display_vec.iter().enumerate(){
+                                                ui.selectable_value(
+                                                    &mut self.display,
+                                                    Some(i),
+                                                    format!("🖵 Display {}  {}x{}", i, display.width as f32 * display.scale_factor, display.height as f32 * display.scale_factor)
+                                                );
+                                            }
+                                        }
+                                        Err(err) => {
+                                            ui.label(RichText::new(err.to_string()).color(Color32::from_rgb(255, 0, 0)));
+                                        }
+                                    }
+                                });
+                            }
+
+                            if self.document.is_some(){
+                                let save_button = ui.add(Button::new("💾 Save as"));
+                                if save_button.clicked() {
+                                    self.save_snapshot();
+                                }
+                            }
+
+                            if self.document.is_some(){
+                                let copy_button = ui.add(Button::new("📄 Copy"));
+                                if copy_button.clicked() {
+                                    self.copy_snapshot();
+                                }
+                            }
+
+                            if self.document.is_some(){
+                                ui.menu_button("🖼 Image", |ui| {
+                                    if ui.button("Resize…").clicked() {
+                                        let background = self.document.as_ref().unwrap().background();
+                                        self.resize_dialog = Some(ResizeDialog::new(background.width(), background.height()));
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("✨ Beautify…").clicked() {
+                                        self.show_beautify = true;
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("🔆 Adjustments…").clicked() {
+                                        self.show_adjustments = true;
+                                        ui.close_menu();
+                                    }
+                                });
+
+                                let zoom_label = match self.zoom {
+                                    Some(zoom) => format!("🔍 {}%", (zoom * 100.).round()),
+                                    None => "🔍 Fit".to_string(),
+                                };
+                                ui.menu_button(zoom_label, |ui| {
+                                    for (label, zoom) in [("Fit", None), ("100%", Some(1.)), ("200%", Some(2.))] {
+                                        if ui.button(label).clicked() {
+                                            self.zoom = zoom;
+                                            self.pan = Vec2::ZERO;
+                                            ui.close_menu();
+                                        }
+                                    }
+                                });
+                            }
+
+                            let settings_button = ui.add(Button::new("🔨 Settings"));
+                            if settings_button.clicked() {
+
+                                for hotkey in self.hotkeys.iter_mut(){
+                                    hotkey.tmp_code = hotkey.code.clone();
+                                    hotkey.tmp_modifier = hotkey.modifier.clone();
+                                }
+
+                                self.show_tools = false;
+                                self.show_settings = true;
+                            }
+
+                            let credits_button = ui.add(Button::new("💻 Credits"));
+                            if credits_button.clicked() {
+                                self.show_tools = false;
+                                self.show_credits = true;
+                            }
+
+                        });
+
+                        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                            let mut top_right_rect = ui.max_rect();
+                            top_right_rect.max.y = top_right_rect.min.y + 36.;
+                            top_right_rect.min.x = top_right_rect.max.x/2.;
+                            top_right_rect = top_right_rect.shrink(4.0);
+                            let mut top_right_panel_ui = ui.child_ui(top_right_rect, *ui.layout());
+    
+                            top_right_panel_ui.horizontal(|ui| {
+                                if self.document.is_some(){
+                                    let tool_toggle_button = ui.add(Button::new("🔧 Show tools"));
+                                    if tool_toggle_button.clicked() {
+                                        self.show_tools = !self.show_tools;
+                                    }
+                                }
+
+                                if let Some(err) = self.capture_error.as_ref() {
+                                    ui.label(RichText::new(format!("⚠ {}", err)).color(Color32::from_rgb(255, 0, 0)));
+                                }
+                            });
+                        });
+                        
+                    });
+
+                    
+                });
+            });
+        }
+    }
+
+    fn render_side_panel(&mut self, ctx: &Context, _frame: &mut Frame) {
+        if self.show_tools{
+            let style = self.get_style();
+            let settings_tool = self.get_settings_tool();
+            if !ctx.input(|i| i.pointer.any_down()) {
+                self.restyling = false;
+            }
+
+            SidePanel::right("right panel")
+            .exact_width(110.)
+            .show(ctx, |ui| {
+                let mut side_rect = ui.max_rect();
+                side_rect.max.x = side_rect.max.x - 12.;
+                side_rect = side_rect.shrink(4.0);
+                let mut side_panel_ui = ui.child_ui(side_rect, *ui.layout());
+
+
+                side_panel_ui.vertical(|ui| {
+                    ui.with_layout(Layout::top_down(Align::Center), |ui| {
+                        let button_size = Vec2::from([40., 40.]);
+                        let tools = [
+                            ("🚫", Tool::None),
+                            ("🖱", Tool::Select),
+                            ("✏", Tool::Pen),
+                            ("🖊", Tool::Highlighter),
+                            ("⟲", Tool::Eraser),
+                            ("▓", Tool::Redact),
+                            ("✂", Tool::Crop),
+                            ("⬜", Tool::Rectangle),
+                            ("⭕", Tool::Ellipse),
+                            ("↗", Tool::Arrow),
+                            ("🗛", Tool::Text),
+                            ("🔢", Tool::Step),
+                            ("💉", Tool::ColorPicker),
+                        ];
+
+                        Grid::new("tools").spacing([6., 6.]).show(ui, |ui| {
+                            for (i, (icon, tool)) in tools.iter().enumerate() {
+                                let button = ui.add(Button::new(*icon).rounding(5.).min_size(button_size));
+                                if button.clicked() {
+                                    self.tool = *tool;
+                                }
+                                if self.tool == *tool {
+                                    button.highlight();
+                                }
+                                if i % 2 == 1 {
+                                    ui.end_row();
+                                }
+                            }
+                        });
                         ui.separator();
 
-                        let redo_button = ui.add(Button::new("↪").rounding(5.).min_size(button_size));
-                        if redo_button.clicked() {
-                            self.redo();
-                        }
+                        ui.horizontal(|ui| {
+                            if ui.add(Button::new("↩").rounding(5.).min_size(button_size)).clicked() {
+                                self.undo();
+                            }
+                            if ui.add(Button::new("↪").rounding(5.).min_size(button_size)).clicked() {
+                                self.redo();
+                            }
+                        });
                         ui.separator();
 
+                        let transforms = [
+                            ("↺", "Rotate left", Transform::RotateLeft),
+                            ("↻", "Rotate right", Transform::RotateRight),
+                            ("🔃", "Rotate 180°", Transform::Rotate180),
+                            ("⬌", "Flip horizontal", Transform::FlipHorizontal),
+                            ("⬍", "Flip vertical", Transform::FlipVertical),
+                        ];
+                        ui.horizontal_wrapped(|ui| {
+                            for (icon, label, transform) in transforms {
+                                if ui.add(Button::new(icon).rounding(5.)).on_hover_text(label).clicked() {
+                                    self.transform(transform);
+                                }
+                            }
+                        });
+                        ui.separator();
 
-                        match self.tool {
-                            Tool::None => none_button.highlight(),
-                            Tool::Pen => pen_button.highlight(),
-                            Tool::Crop => crop_button.highlight(),
-                        };
-                        
+                        if let (Tool::Select, Some(index)) = (self.tool, self.selection) {
+                            ui.horizontal(|ui| {
+                                let document = self.document.as_mut().unwrap();
+                                let last = document.annotations.len() - 1;
+                                if ui.add(Button::new("⏫").rounding(5.)).on_hover_text("Bring to front").clicked() {
+                                    document.reorder(index, last);
+                                    self.selection = Some(last);
+                                }
+                                if ui.add(Button::new("⏬").rounding(5.)).on_hover_text("Send to back").clicked() {
+                                    document.reorder(index, 0);
+                                    self.selection = Some(0);
+                                }
+                                if ui.add(Button::new("🗑").rounding(5.)).on_hover_text("Delete").clicked() {
+                                    document.remove(index);
+                                    self.selection = None;
+                                }
+                            });
+                            ui.separator();
+                        }
                     });
 
-                    if self.tool == Tool::Pen {
+                    if settings_tool == Tool::Pen || settings_tool == Tool::Rectangle || settings_tool == Tool::Ellipse || settings_tool == Tool::Arrow {
                         ui.with_layout(Layout::bottom_up(Align::Center), |ui| {
                             ui.add_space(10.);
 
+                            if settings_tool == Tool::Rectangle || settings_tool == Tool::Ellipse {
+                                ui.checkbox(&mut self.shape_filled, "Fill");
+                                ui.add_space(5.);
+                            }
+
+                            if self.tool == Tool::Pen {
+                                ui.checkbox(&mut self.pen_smoothing, "Smooth");
+                                ui.add_space(5.);
+                            }
+
+                            if settings_tool == Tool::Arrow {
+                                ui.horizontal(|ui| {
+                                    ui.selectable_value(&mut self.arrow_style, ArrowStyle::Open, "▷");
+                                    ui.selectable_value(&mut self.arrow_style, ArrowStyle::Filled, "▶");
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.selectable_value(&mut self.arrow_heads, ArrowHeads::Single, "→");
+                                    ui.selectable_value(&mut self.arrow_heads, ArrowHeads::Double, "↔");
+                                });
+                                ui.add_space(5.);
+                            }
+
                             ui.add(DragValue::new(&mut self.pen_size));
                             if self.pen_size<1{
                                 self.pen_size = 1;
@@ -534,52 +1837,562 @@ impl SnapRustApp {
                             ui.separator();
                         });
                     }
+
+                    if settings_tool == Tool::Highlighter {
+                        ui.with_layout(Layout::bottom_up(Align::Center), |ui| {
+                            ui.add_space(10.);
+                            ui.add(DragValue::new(&mut self.highlighter_size).clamp_range(1..=50));
+                            ui.add_space(5.);
+                            ui.color_edit_button_rgb(&mut self.highlighter_color);
+                            ui.add_space(5.);
+                            ui.separator();
+                        });
+                    }
+
+                    if settings_tool == Tool::ColorPicker {
+                        ui.with_layout(Layout::bottom_up(Align::Center), |ui| {
+                            ui.add_space(10.);
+
+                            if let Some(color) = self.picked_color {
+                                let text = format_color(color, self.color_format);
+                                if ui.add(Button::new(RichText::new(text.clone()).small())).on_hover_text("Copy").clicked() {
+                                    if let Ok(mut clipboard) = Clipboard::new() {
+                                        clipboard.set_text(text).ok();
+                                    }
+                                }
+                                ui.add_space(5.);
+                            }
+
+                            ui.selectable_value(&mut self.color_format, ColorFormat::Hsl, "HSL");
+                            ui.selectable_value(&mut self.color_format, ColorFormat::Rgb, "RGB");
+                            ui.selectable_value(&mut self.color_format, ColorFormat::Hex, "Hex");
+                            ui.add_space(5.);
+                            ui.separator();
+                        });
+                    }
+
+                    if settings_tool == Tool::Step {
+                        ui.with_layout(Layout::bottom_up(Align::Center), |ui| {
+                            ui.add_space(10.);
+
+                            let next_step = self.document.as_ref().map_or(1, |document| document.next_step);
+                            let reset_button = ui.add(Button::new(format!("⟲ {}", next_step)));
+                            if reset_button.on_hover_text("Restart the numbering at 1").clicked() {
+                                if let Some(document) = self.document.as_mut() {
+                                    document.reset_steps();
+                                }
+                            }
+                            ui.add_space(5.);
+
+                            ui.add(DragValue::new(&mut self.step_size).clamp_range(6..=80));
+                            ui.add_space(5.);
+                            ui.color_edit_button_rgb(&mut self.pen_color);
+                            ui.add_space(5.);
+                            ui.separator();
+                        });
+                    }
+
+                    if settings_tool == Tool::Crop {
+                        ui.with_layout(Layout::bottom_up(Align::Center), |ui| {
+                            ui.add_space(10.);
+
+                            let has_rect = self.crop_rect.is_some();
+                            ui.horizontal(|ui| {
+                                if ui.add_enabled(has_rect, Button::new("✔")).on_hover_text("Apply (Enter)").clicked() {
+                                    self.apply_crop();
+                                }
+                                if ui.add_enabled(has_rect, Button::new("🗙")).on_hover_text("Cancel (Esc)").clicked() {
+                                    self.crop_rect = None;
+                                }
+                            });
+                            ui.add_space(5.);
+
+                            let background = self.document.as_ref().unwrap().background();
+                            let image_size = Vec2::new(background.width() as f32, background.height() as f32);
+                            let aspect = self.crop_aspect.value();
+
+                            // Position and size in whole pixels, bottom up
+                            let values = self.crop_rect.map_or([0.; 4], |rect| {
+                                let (x, y) = (rect.min.x.round(), rect.min.y.round());
+                                [rect.max.y.round() - y, rect.max.x.round() - x, y, x]
+                            });
+                            let mut new_values = values;
+                            for (label, value) in ["H", "W", "Y", "X"].iter().zip(new_values.iter_mut()) {
+                                ui.add_enabled_ui(has_rect, |ui| {
+                                    ui.horizontal(|ui| {
+                                        ui.label(*label);
+                                        ui.add(DragValue::new(value).clamp_range(0.0..=f32::MAX).speed(1.).fixed_decimals(0));
+                                    });
+                                });
+                            }
+                            if has_rect && new_values != values {
+                                let [mut height, mut width, y, x] = new_values;
+                                if let Some(aspect) = aspect {
+                                    if width != values[1] {
+                                        height = width / aspect;
+                                    }
+                                    else if height != values[0] {
+                                        width = height * aspect;
+                                    }
+                                }
+                                let rect = Rect::from_min_size(Pos2::new(x, y), Vec2::new(width, height));
+                                self.crop_rect = Some(fit_crop_rect(rect, aspect, image_size));
+                            }
+                            ui.add_space(5.);
+
+                            for crop_aspect in [AspectRatio::Standard, AspectRatio::Wide, AspectRatio::Square, AspectRatio::Free] {
+                                let button = ui.selectable_value(&mut self.crop_aspect, crop_aspect, crop_aspect.label());
+                                if button.clicked() {
+                                    self.crop_rect = self.crop_rect.map(|rect| fit_crop_rect(rect, crop_aspect.value(), image_size));
+                                }
+                            }
+                            ui.add_space(5.);
+                            ui.separator();
+                        });
+                    }
+
+                    if settings_tool == Tool::Eraser {
+                        ui.with_layout(Layout::bottom_up(Align::Center), |ui| {
+                            ui.add_space(10.);
+                            ui.add(DragValue::new(&mut self.eraser_size).clamp_range(1..=50));
+                            ui.add_space(5.);
+                            ui.separator();
+                        });
+                    }
+
+                    if settings_tool == Tool::Redact {
+                        ui.with_layout(Layout::bottom_up(Align::Center), |ui| {
+                            ui.add_space(10.);
+
+                            if self.redact_style == RedactStyle::Fill {
+                                ui.color_edit_button_rgb(&mut self.redact_color);
+                            }
+                            else {
+                                let min_strength = if self.redact_style == RedactStyle::Blur { MIN_REDACT_BLUR } else { MIN_REDACT_BLOCK };
+                                self.redact_strength = self.redact_strength.max(min_strength);
+                                ui.add(DragValue::new(&mut self.redact_strength).clamp_range(min_strength..=64));
+                            }
+                            ui.add_space(5.);
+
+                            ui.selectable_value(&mut self.redact_style, RedactStyle::Fill, "Fill");
+                            ui.selectable_value(&mut self.redact_style, RedactStyle::Pixelate, "Pixelate");
+                            ui.selectable_value(&mut self.redact_style, RedactStyle::Blur, "Blur");
+                            ui.add_space(5.);
+                            ui.separator();
+                        });
+                    }
+
+                    if settings_tool == Tool::Text {
+                        ui.with_layout(Layout::bottom_up(Align::Center), |ui| {
+                            ui.add_space(10.);
+
+                            let font_button = ui.add(Button::new("🗁 Font"));
+                            if font_button.on_hover_text(self.font_name.clone()).clicked() {
+                                self.load_font(ui.ctx());
+                            }
+                            ui.add_space(5.);
+
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.text_background, "");
+                                ui.color_edit_button_rgb(&mut self.text_background_color);
+                            });
+                            ui.add_space(5.);
+
+                            ui.add(DragValue::new(&mut self.text_size).clamp_range(8..=200));
+                            ui.add_space(5.);
+                            ui.color_edit_button_rgb(&mut self.pen_color);
+                            ui.add_space(5.);
+                            ui.separator();
+                        });
+                    }
+                });
+            });
+
+            if self.get_style() != style {
+                self.restyle_selection(ctx);
+            }
+        }
+    }
+
+    fn render_resize_dialog(&mut self, ctx: &Context) {
+        let scale_factor = self.document.as_ref().unwrap().scale_factor();
+        let mut open = true;
+        let mut apply = false;
+        let mut cancel = false;
+
+        if let Some(dialog) = self.resize_dialog.as_mut() {
+            Window::new("Resize")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                Grid::new("resize").num_columns(2).spacing([10., 8.]).show(ui, |ui| {
+                    ui.label("Width");
+                    if ui.add(DragValue::new(&mut dialog.width).clamp_range(1..=MAX_IMAGE_SIZE).suffix(" px")).changed() {
+                        dialog.update_height();
+                    }
+                    ui.end_row();
+
+                    ui.label("Height");
+                    if ui.add(DragValue::new(&mut dialog.height).clamp_range(1..=MAX_IMAGE_SIZE).suffix(" px")).changed() {
+                        dialog.update_width();
+                    }
+                    ui.end_row();
+
+                    ui.label("Scale");
+                    let mut percentage = dialog.get_percentage();
+                    if ui.add(DragValue::new(&mut percentage).clamp_range(1..=800).max_decimals(1).suffix(" %")).changed() {
+                        dialog.set_percentage(percentage);
+                    }
+                    ui.end_row();
+
+                    ui.label("Filter");
+                    ComboBox::from_id_source("resize filter")
+                    .selected_text(get_filter_label(dialog.filter))
+                    .show_ui(ui, |ui| {
+                        for filter in [FilterType::Nearest, FilterType::Triangle, FilterType::CatmullRom, FilterType::Lanczos3] {
+                            ui.selectable_value(&mut dialog.filter, filter, get_filter_label(filter));
+                        }
+                    });
+                    ui.end_row();
+                });
+
+                if ui.checkbox(&mut dialog.lock_aspect, "Lock aspect ratio").changed() {
+                    dialog.update_height();
+                }
+
+                // Captures of HiDPI displays have more pixels than the screen has points, until they
+                // are downscaled once
+                let logical_width = (dialog.original.0 as f32 / scale_factor).round() as u32;
+                let logical_button = ui.add_enabled(logical_width < dialog.original.0, Button::new(format!("Downscale to logical resolution ({}x)", (scale_factor * 100.).round() / 100.)));
+                if logical_button.clicked() {
+                    dialog.set_percentage(100. / scale_factor);
+                }
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    if ui.button("Resize").clicked() {
+                        apply = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+        }
+
+        if apply {
+            let dialog = self.resize_dialog.take().unwrap();
+            self.selection = None;
+            self.crop_rect = None;
+            self.document.as_mut().unwrap().resize(dialog.width, dialog.height, dialog.filter);
+        }
+        else if cancel || !open {
+            self.resize_dialog = None;
+        }
+    }
+
+    fn render_beautify_dialog(&mut self, ctx: &Context) {
+        let document = self.document.as_ref().unwrap();
+        let outdated = match self.beautify_preview.as_ref() {
+            Some((settings, revision, _)) => *settings != self.beautify || *revision != document.revision(),
+            None => true,
+        };
+
+        // The preview is made from a small copy, with the sizes scaled to match
+        if outdated {
+            let image = document.flatten();
+            let scale = (BEAUTIFY_PREVIEW_SIZE / image.width().max(image.height()) as f32).min(1.);
+            let small_image = image.resize((image.width() as f32 * scale) as u32, (image.height() as f32 * scale) as u32, FilterType::Triangle);
+            let preview = beautify_image(&small_image, &self.beautify, scale);
+
+            let color_image = ColorImage::from_rgba_unmultiplied(
+                [preview.width() as usize, preview.height() as usize],
+                preview.as_bytes(),
+            );
+            let texture_handle = ctx.load_texture("beautify preview", color_image, Default::default());
+            self.beautify_preview = Some((self.beautify, document.revision(), texture_handle));
+        }
+
+        let mut open = self.show_beautify;
+        let beautify = &mut self.beautify;
+        let texture_handle = &self.beautify_preview.as_ref().unwrap().2;
+
+        Window::new("Beautify")
+        .collapsible(false)
+        .resizable(false)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            ui.horizontal_top(|ui| {
+                ui.vertical(|ui| {
+                    ui.checkbox(&mut beautify.enabled, "Apply when saving and copying");
+                    ui.add_space(5.);
+
+                    Grid::new("beautify").num_columns(2).spacing([10., 8.]).show(ui, |ui| {
+                        ui.label("Padding");
+                        ui.add(DragValue::new(&mut beautify.padding).clamp_range(0..=400).suffix(" px"));
+                        ui.end_row();
+
+                        ui.label("Background");
+                        ui.horizontal(|ui| {
+                            ui.color_edit_button_srgb(&mut beautify.background);
+                            ui.checkbox(&mut beautify.gradient, "Gradient");
+                            if beautify.gradient {
+                                ui.color_edit_button_srgb(&mut beautify.gradient_color);
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Corner radius");
+                        ui.add(DragValue::new(&mut beautify.corner_radius).clamp_range(0..=200).suffix(" px"));
+                        ui.end_row();
+
+                        ui.label("Shadow blur");
+                        ui.add(DragValue::new(&mut beautify.shadow_blur).clamp_range(0..=100).suffix(" px"));
+                        ui.end_row();
+
+                        ui.label("Shadow offset");
+                        ui.horizontal(|ui| {
+                            ui.add(DragValue::new(&mut beautify.shadow_offset.0).clamp_range(-200..=200).prefix("x "));
+                            ui.add(DragValue::new(&mut beautify.shadow_offset.1).clamp_range(-200..=200).prefix("y "));
+                        });
+                        ui.end_row();
+
+                        ui.label("Shadow opacity");
+                        ui.add(Slider::new(&mut beautify.shadow_opacity, 0.0..=1.0));
+                        ui.end_row();
+
+                        ui.label("Title bar");
+                        ui.checkbox(&mut beautify.window_chrome, "");
+                        ui.end_row();
+                    });
                 });
+
+                let mut size = texture_handle.size_vec2();
+                size *= (Vec2::splat(BEAUTIFY_PREVIEW_SIZE * 0.75) / size).min_elem().min(1.);
+                ui.image(texture_handle.id(), size);
+            });
+        });
+
+        self.show_beautify = open;
+    }
+
+    // Previews the pending adjustments on a small copy of the snapshot, running them on the whole
+    // snapshot at every step of a drag would be too slow on large captures
+    fn update_adjust_preview(&mut self) {
+        let adjustments = match self.adjust_pending {
+            Some(adjustments) => adjustments,
+            None => {
+                self.adjust_preview = None;
+                return;
+            }
+        };
+
+        let document = self.document.as_ref().unwrap();
+        let background = document.background();
+        if self.adjust_proxy.as_ref().map_or(true, |(revision, _)| *revision != document.revision()) {
+            let scale = (ADJUST_PROXY_SIZE / background.width().max(background.height()) as f32).min(1.);
+            let width = ((background.width() as f32 * scale) as u32).max(1);
+            let height = ((background.height() as f32 * scale) as u32).max(1);
+            self.adjust_proxy = Some((document.revision(), background.resize_exact(width, height, FilterType::Triangle)));
+            self.adjust_preview = None;
+        }
+
+        if self.adjust_preview.as_ref().map_or(true, |(previewed, _)| *previewed != adjustments) {
+            let proxy = &self.adjust_proxy.as_ref().unwrap().1;
+            let scale = proxy.width() as f32 / background.width() as f32;
+            self.adjust_preview = Some((adjustments, adjust_image(proxy, &adjustments, scale)));
+        }
+    }
+
+    fn render_adjustments_dialog(&mut self, ctx: &Context) {
+        let document = self.document.as_mut().unwrap();
+        let mut adjustments = self.adjust_pending.unwrap_or(document.adjustments());
+        let mut open = self.show_adjustments;
+        let show_before = &mut self.show_before;
+
+        Window::new("Adjustments")
+        .collapsible(false)
+        .resizable(false)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            Grid::new("adjustments").num_columns(2).spacing([10., 8.]).show(ui, |ui| {
+                ui.label("Brightness");
+                ui.add(Slider::new(&mut adjustments.brightness, -100.0..=100.0));
+                ui.end_row();
+
+                ui.label("Contrast");
+                ui.add(Slider::new(&mut adjustments.contrast, -100.0..=100.0));
+                ui.end_row();
+
+                ui.label("Gamma");
+                ui.add(Slider::new(&mut adjustments.gamma, 0.2..=5.0).logarithmic(true));
+                ui.end_row();
+
+                ui.label("Saturation");
+                ui.add_enabled(!adjustments.grayscale, Slider::new(&mut adjustments.saturation, -100.0..=100.0));
+                ui.end_row();
+
+                ui.label("Grayscale");
+                ui.checkbox(&mut adjustments.grayscale, "");
+                ui.end_row();
+
+                ui.label("Invert");
+                ui.checkbox(&mut adjustments.invert, "");
+                ui.end_row();
+
+                ui.label("Sharpen");
+                ui.add(Slider::new(&mut adjustments.sharpen_amount, 0.0..=5.0));
+                ui.end_row();
+
+                ui.label("Sharpen radius");
+                ui.add(Slider::new(&mut adjustments.sharpen_radius, 0.1..=20.0).suffix(" px"));
+                ui.end_row();
+
+                ui.label("Sharpen threshold");
+                ui.add(Slider::new(&mut adjustments.sharpen_threshold, 0..=255));
+                ui.end_row();
+            });
+
+            ui.add_space(5.);
+            ui.horizontal(|ui| {
+                ui.toggle_value(show_before, "◑ Before");
+                if ui.button("↺ Reset").clicked() {
+                    adjustments = Adjustments::default();
+                }
             });
+        });
+
+        self.show_adjustments = open;
+        if !open {
+            self.show_before = false;
+        }
+
+        // The whole snapshot is only adjusted once the pointer is released
+        if adjustments == document.adjustments() {
+            self.adjust_pending = None;
+        }
+        else if ctx.input(|i| i.pointer.any_down()) {
+            self.adjust_pending = Some(adjustments);
+        }
+        else {
+            document.adjust(adjustments);
+            self.adjust_pending = None;
+            ctx.request_repaint();
         }
     }
 
     fn render_central_panel(&mut self, ctx: &Context, _frame: &mut Frame) {
+        if self.document.is_some() {
+            self.update_adjust_preview();
+        }
 
         CentralPanel::default().show(ctx, |ui| {
             if !self.show_settings && !self.show_credits{
-                if self.snapshot.is_some(){
+                if self.document.is_some(){
                     
                     ui.with_layout(Layout::top_down(Align::Center), |ui| {
-                        let snapshot = self.snapshot.as_ref().unwrap();
+                        let document = self.document.as_ref().unwrap();
+                        let background = document.background();
+                        let real_image_size = [background.width() as usize, background.height() as usize];
+
+                        // While dragging an adjustment the texture is the smaller preview, the
+                        // tools keep working in the pixels of the snapshot
+                        let shown_image = match (self.show_before, self.adjust_preview.as_ref()) {
+                            (true, _) => background,
+                            (false, Some((_, preview))) => preview,
+                            (false, None) => document.adjusted(),
+                        };
                         let color_image = ColorImage::from_rgba_unmultiplied(
-                            [snapshot.width() as usize, snapshot.height() as usize],
-                            snapshot.as_bytes(),
+                            [shown_image.width() as usize, shown_image.height() as usize],
+                            shown_image.as_bytes(),
                         );
-    
+
+                        let panel_rect = ui.max_rect();
+                        let pixels_per_point = ui.ctx().pixels_per_point();
+                        let mut view = match self.zoom {
+                            Some(zoom) => ViewTransform::centered(panel_rect.center() + self.pan, zoom / pixels_per_point, real_image_size),
+                            None => {
+                                let fit_scale = (panel_rect.width() / real_image_size[0] as f32).min(panel_rect.height() / real_image_size[1] as f32);
+                                ViewTransform::centered(panel_rect.center(), fit_scale, real_image_size)
+                            }
+                        };
+
+                        // The wheel zooms around the cursor
+                        let hover_pos = ui.input(|i| i.pointer.hover_pos()).filter(|_| ui.rect_contains_pointer(panel_rect));
+                        if let Some(pos) = hover_pos {
+                            let factor = ui.input(|i| i.zoom_delta() * (i.scroll_delta.y * ZOOM_SPEED).exp());
+                            if factor != 1. {
+                                let zoom = (view.scale * pixels_per_point * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+                                view = view.zoom_around(pos, zoom / pixels_per_point);
+                                self.zoom = Some(zoom);
+                            }
+                        }
+
+                        // Dragging with the middle button, or the left one while Space is held, pans
+                        let space_down = ui.input(|i| i.key_down(Key::Space)) && !ui.ctx().wants_keyboard_input();
+                        if hover_pos.is_some() && ui.input(|i| i.pointer.button_pressed(PointerButton::Middle) || (space_down && i.pointer.primary_pressed())) {
+                            self.panning = true;
+                        }
+                        else if self.panning && !ui.input(|i| i.pointer.any_down() || i.pointer.any_released()) {
+                            self.panning = false;
+                        }
+                        if self.panning {
+                            view.origin += ui.input(|i| i.pointer.delta());
+                            self.zoom = Some(view.scale * pixels_per_point);
+                        }
+
+                        // Keep the panel center on the image
+                        if self.zoom.is_some() {
+                            let image_rect = view.get_image_rect(real_image_size);
+                            let limit = image_rect.size() / 2.;
+                            self.pan = (image_rect.center() - panel_rect.center()).clamp(-limit, limit);
+                            view = ViewTransform::centered(panel_rect.center() + self.pan, view.scale, real_image_size);
+                        }
+
+                        // Show the pixels as squares when they are bigger than the screen ones
+                        let texture_options = if view.scale * pixels_per_point > 1. { TextureOptions::NEAREST } else { TextureOptions::LINEAR };
                         let texture_handle = ui.ctx().load_texture(
                             "screenshot",
                             color_image,
-                            Default::default(),
+                            texture_options,
                         );
-    
-                        
-                        let available_size = ui.available_size();
-                        let aspect_ratio = texture_handle.aspect_ratio();
-                        let mut size = available_size;
-                        size.x = size.y * aspect_ratio;
-    
-                        if size.x > available_size.x || size.y > available_size.y {
-                            size = available_size;
-                            size.y = size.x / aspect_ratio;
-                        }
-    
-    
-                        let image_rect = Rect::from_center_size(ui.max_rect().center(), size);
+
+                        let image_rect = view.get_image_rect(real_image_size);
                         let mut image_ui = ui.child_ui(image_rect, *ui.layout());
-    
-    
-    
-                        let image_response = image_ui.add(ImageButton::new(texture_handle.id(), size).frame(false).sense(Sense::click_and_drag()));
+
+                        let image_response = image_ui.add(ImageButton::new(texture_handle.id(), image_rect.size()).frame(false).sense(Sense::click_and_drag()));
+
+                        let painter = image_ui.painter().with_clip_rect(image_response.rect);
+                        for annotation in self.document.as_ref().unwrap().annotations.iter() {
+                            annotation.paint(&painter, image_response.rect, real_image_size, texture_handle.id());
+                        }
+
+                        if self.tool != Tool::Text && self.text_box.is_some() {
+                            self.commit_text();
+                        }
+                        if self.tool != Tool::Select {
+                            self.selection = None;
+                        }
+                        if self.tool != Tool::Crop {
+                            self.crop_rect = None;
+                        }
                         
-                        if self.tool != Tool::None {
-                            self.update_editing(&mut image_ui, image_response, texture_handle.size()); 
+                        if self.panning {
+                            ui.ctx().set_cursor_icon(CursorIcon::Grabbing);
                         }
+                        else if self.tool != Tool::None {
+                            self.update_editing(&mut image_ui, image_response, real_image_size, texture_handle.id());
+                        }
+                        if space_down && hover_pos.is_some() && !self.panning {
+                            ui.ctx().set_cursor_icon(CursorIcon::Grab);
+                        }
+                    });
+                }
+                else if let Some(err) = self.capture_error.as_ref() {
+                    ui.with_layout(Layout::centered_and_justified(Direction::TopDown), |ui| {
+                        ui.label(RichText::new(format!("⚠ {}", err)).color(Color32::from_rgb(255, 0, 0)));
                     });
                 }
                 else {
@@ -602,55 +2415,58 @@ impl SnapRustApp {
                 
                 let mut shortcut_ui = ui.child_ui(shortcut_rect, *ui.layout());
 
-                shortcut_ui.vertical(|ui| {                    
-                    for (i, hotkey) in self.hotkeys.iter_mut().enumerate() {
-                        ui.horizontal(|ui| {
+                shortcut_ui.vertical(|ui| {
+                    // Leave room for the buttons below the list
+                    ScrollArea::vertical().max_height(shortcut_rect.height() - 40.).show(ui, |ui| {
+                        for (i, hotkey) in self.hotkeys.iter_mut().enumerate() {
+                            ui.horizontal(|ui| {
 
-                            ui.label(format!("{}:", hotkey.label.clone()));
+                                ui.label(format!("{}:", hotkey.label.clone()));
 
-                            egui::ComboBox::from_id_source(i+2)
-                            .width(80.)
-                            .selected_text(format!("{:}", hotkey.tmp_modifier))
-                            .show_ui(ui,|ui|  {                                   
-                                ui.selectable_value(&mut hotkey.tmp_modifier, "CTRL".to_string(),  "CTRL");
-                                ui.selectable_value(&mut hotkey.tmp_modifier, "SHIFT".to_string(),  "SHIFT");
-                                ui.selectable_value(&mut hotkey.tmp_modifier, "ALT".to_string(),  "ALT");
-                            });
+                                egui::ComboBox::from_id_source(i+2)
+                                .width(80.)
+                                .selected_text(format!("{:}", hotkey.tmp_modifier))
+                                .show_ui(ui,|ui|  {                                   
+                                    ui.selectable_value(&mut hotkey.tmp_modifier, "CTRL".to_string(),  "CTRL");
+                                    ui.selectable_value(&mut hotkey.tmp_modifier, "SHIFT".to_string(),  "SHIFT");
+                                    ui.selectable_value(&mut hotkey.tmp_modifier, "ALT".to_string(),  "ALT");
+                                });
 
-                            ui.label(" + ");
+                                ui.label(" + ");
 
-                            let tmp_tmp_code = hotkey.tmp_code.clone();
+                                let tmp_tmp_code = hotkey.tmp_code.clone();
                             
-                            ui.text_edit_singleline(&mut hotkey.tmp_code);
+                                ui.text_edit_singleline(&mut hotkey.tmp_code);
 
-                            if hotkey.tmp_code.len() == 0{
-                                hotkey.tmp_code = tmp_tmp_code.clone();
-                            }
+                                if hotkey.tmp_code.len() == 0{
+                                    hotkey.tmp_code = tmp_tmp_code.clone();
+                                }
 
-                            if hotkey.tmp_code.len() > 1{
-                                let mut char_iterator = hotkey.tmp_code.chars();
-                                let first_char = char_iterator.next().unwrap().to_string();
-                                let second_char = char_iterator.next().unwrap().to_string();
+                                if hotkey.tmp_code.len() > 1{
+                                    let mut char_iterator = hotkey.tmp_code.chars();
+                                    let first_char = char_iterator.next().unwrap().to_string();
+                                    let second_char = char_iterator.next().unwrap().to_string();
 
-                                let key = hotkeys_utils::string_to_key(second_char.as_str());
+                                    let key = string_to_key(second_char.as_str());
 
-                                if !key.is_none(){
-                                    if first_char == tmp_tmp_code{
-                                        hotkey.tmp_code = second_char;
+                                    if !key.is_none(){
+                                        if first_char == tmp_tmp_code{
+                                            hotkey.tmp_code = second_char;
+                                        }
+                                        else if second_char == tmp_tmp_code{
+                                            hotkey.tmp_code = first_char;
+                                        }
                                     }
-                                    else if second_char == tmp_tmp_code{
-                                        hotkey.tmp_code = first_char;
+                                    else {
+                                        hotkey.tmp_code = tmp_tmp_code;
                                     }
                                 }
-                                else {
-                                    hotkey.tmp_code = tmp_tmp_code;
-                                }
-                            }
 
-                            hotkey.tmp_code = hotkey.tmp_code.to_uppercase();
-                        });
-                        ui.add_space(10.);
-                    }
+                                hotkey.tmp_code = hotkey.tmp_code.to_uppercase();
+                            });
+                            ui.add_space(10.);
+                        }
+                    });
                 });
 
                 shortcut_ui.with_layout(Layout::right_to_left(Align::Max), |ui| {
@@ -748,15 +2564,38 @@ impl SnapRustApp {
 
 
 
+// Dims the part of outer around inner
+fn dim_outside(painter: &Painter, outer: Rect, inner: Rect) {
+    let dim_color = Color32::from_rgba_premultiplied(0, 0, 0, 120);
+    painter.rect_filled(Rect::from_min_max(outer.left_top(), Pos2::new(outer.right(), inner.top())), 0., dim_color);
+    painter.rect_filled(Rect::from_min_max(Pos2::new(outer.left(), inner.bottom()), outer.right_bottom()), 0., dim_color);
+    painter.rect_filled(Rect::from_min_max(Pos2::new(outer.left(), inner.top()), inner.left_bottom()), 0., dim_color);
+    painter.rect_filled(Rect::from_min_max(inner.right_top(), Pos2::new(outer.right(), inner.bottom())), 0., dim_color);
+}
+
 impl eframe::App for SnapRustApp {
     fn update(&mut self, ctx: &Context, frame: &mut Frame) {
         match self.rx.try_recv() {
-            Ok(snapshot) => {
-                self.snapshots_undo.clear();
-                self.snapshots_redo.clear();
+            Ok(Ok(snapshot)) => {
+                if self.pending_region {
+                    self.pending_region = false;
+                    self.start_region_selection(frame, snapshot);
+                }
+                else {
+                    let mut document = Document::new(snapshot);
+                    document.set_scale_factor(self.pending_scale_factor);
+                    self.document = Some(document);
+                    self.adjust_pending = None;
+                    self.adjust_proxy = None;
+                    self.zoom = None;
+                    self.pan = Vec2::ZERO;
+                }
 
-                self.snapshot = Some(snapshot.clone());
-                self.snapshots_undo.push_back(snapshot);
+                frame.set_visible(true);
+            }
+            Ok(Err(err)) => {
+                self.pending_region = false;
+                self.capture_error = Some(err);
 
                 frame.set_visible(true);
             }
@@ -764,9 +2603,30 @@ impl eframe::App for SnapRustApp {
         }
 
         self.register_hotkey_listener(ctx, frame);
+
+        if self.countdown.is_some() {
+            self.render_countdown(ctx, frame);
+            return;
+        }
+
+        if self.region_selection.is_some() {
+            self.render_region_overlay(ctx, frame);
+            return;
+        }
+
+        self.enforce_min_window_size(frame);
         
         self.render_top_panel(ctx, frame);
         self.render_central_panel(ctx, frame);
         self.render_side_panel(ctx, frame);
+        if self.resize_dialog.is_some() {
+            self.render_resize_dialog(ctx);
+        }
+        if self.show_beautify && self.document.is_some() {
+            self.render_beautify_dialog(ctx);
+        }
+        if self.show_adjustments && self.document.is_some() {
+            self.render_adjustments_dialog(ctx);
+        }
     }
 }
@@ -0,0 +1,650 @@
+use eframe::egui::Pos2;
+use image::{DynamicImage, RgbaImage};
+use image::imageops::replace;
+use screenshots::display_info::DisplayInfo;
+use screenshots::Screen;
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::tool_utils::{get_real_image_pos, crop_image, ViewTransform};
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum CaptureMode {
+    Display,
+    Region,
+    Window
+}
+
+impl CaptureMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CaptureMode::Display => "🖵 Display",
+            CaptureMode::Region => "⬚ Region",
+            CaptureMode::Window => "🗖 Window",
+        }
+    }
+}
+
+pub struct RegionSelection {
+    pub frozen: DynamicImage,
+    pub start: Option<Pos2>,
+    pub end: Option<Pos2>,
+}
+
+impl RegionSelection {
+    pub fn new(frozen: DynamicImage) -> Self {
+        RegionSelection {
+            frozen: frozen,
+            start: None,
+            end: None
+        }
+    }
+}
+
+// Returns (x, y, width, height) in image pixels, clamped to the image bounds
+pub fn get_region_rect(start: Pos2, end: Pos2, view: &ViewTransform, real_image_size: [usize; 2]) -> (u32, u32, u32, u32) {
+    let image_start = get_real_image_pos(start, view);
+    let image_end = get_real_image_pos(end, view);
+
+    let min_x = image_start.x.min(image_end.x).max(0.).min(real_image_size[0] as f32);
+    let min_y = image_start.y.min(image_end.y).max(0.).min(real_image_size[1] as f32);
+    let max_x = image_start.x.max(image_end.x).max(0.).min(real_image_size[0] as f32);
+    let max_y = image_start.y.max(image_end.y).max(0.).min(real_image_size[1] as f32);
+
+    return (min_x.round() as u32, min_y.round() as u32, (max_x - min_x).round() as u32, (max_y - min_y).round() as u32);
+}
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Display {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32,
+}
+
+impl From<&DisplayInfo> for Display {
+    fn from(display_info: &DisplayInfo) -> Self {
+        Display {
+            x: display_info.x,
+            y: display_info.y,
+            width: display_info.width,
+            height: display_info.height,
+            scale_factor: display_info.scale_factor
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum CaptureRegion {
+    Display(usize),
+    AllDisplays,
+    // x, y, width, height in virtual desktop pixels
+    Area(i32, i32, u32, u32),
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum CaptureError {
+    NoDisplays,
+    InvalidDisplay(usize),
+    WindowNotFound,
+    InvalidArea,
+    Backend(String),
+    Io(String),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::NoDisplays => write!(f, "No display available"),
+            CaptureError::InvalidDisplay(display) => write!(f, "Display {} is not available", display),
+            CaptureError::WindowNotFound => write!(f, "The selected window is no longer available"),
+            CaptureError::InvalidArea => write!(f, "The capture area is outside of every display"),
+            CaptureError::Backend(err) => write!(f, "Screen capture failed: {}", err),
+            CaptureError::Io(err) => write!(f, "Failed to read capture: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+pub trait CaptureSource: Send + Sync {
+    fn list_displays(&self) -> Result<Vec<Display>, CaptureError>;
+    fn capture(&self, region: CaptureRegion) -> Result<DynamicImage, CaptureError>;
+}
+
+// Picks the file based fake when SNAP_RUST_CAPTURE_DIR is set, the real screens otherwise
+pub fn get_capture_source() -> Arc<dyn CaptureSource> {
+    return match env::var("SNAP_RUST_CAPTURE_DIR") {
+        Ok(dir) => Arc::new(FileCaptureSource::new(PathBuf::from(dir))),
+        Err(_) => Arc::new(ScreenshotsCaptureSource),
+    };
+}
+
+// Scale factor of the display the region is on, the largest one when it covers all of them
+pub fn get_capture_scale_factor(displays: &[Display], region: CaptureRegion) -> f32 {
+    return match region {
+        CaptureRegion::Display(display) => displays.get(display).map_or(1., |display| display.scale_factor),
+        CaptureRegion::AllDisplays => displays.iter().map(|display| display.scale_factor).fold(1., f32::max),
+        CaptureRegion::Area(x, y, width, height) => {
+            get_area_display(displays, x, y, width, height).map_or(1., |display| displays[display].scale_factor)
+        }
+    };
+}
+
+// Index of the display an area is captured from, the one under its center. The area is in screen
+// pixels while the displays are laid out in logical pixels.
+pub fn get_area_display(displays: &[Display], x: i32, y: i32, width: u32, height: u32) -> Option<usize> {
+    let center = (x + width as i32 / 2, y + height as i32 / 2);
+    return displays.iter().position(|display| {
+        let center_x = (center.0 as f32 / display.scale_factor) as i32;
+        let center_y = (center.1 as f32 / display.scale_factor) as i32;
+        center_x >= display.x && center_x < display.x + display.width as i32 && center_y >= display.y && center_y < display.y + display.height as i32
+    });
+}
+
+
+pub struct ScreenshotsCaptureSource;
+
+impl CaptureSource for ScreenshotsCaptureSource {
+    fn list_displays(&self) -> Result<Vec<Display>, CaptureError> {
+        let display_vec = DisplayInfo::all().map_err(|err| CaptureError::Backend(err.to_string()))?;
+        return Ok(display_vec.iter().map(Display::from).collect());
+    }
+
+    fn capture(&self, region: CaptureRegion) -> Result<DynamicImage, CaptureError> {
+        let display_vec = DisplayInfo::all().map_err(|err| CaptureError::Backend(err.to_string()))?;
+        if display_vec.is_empty() {
+            return Err(CaptureError::NoDisplays);
+        }
+
+        return match region {
+            CaptureRegion::Display(display) => {
+                let display_info = display_vec.get(display).ok_or(CaptureError::InvalidDisplay(display))?;
+                let image_buffer = Screen::new(display_info).capture().map_err(|err| CaptureError::Backend(err.to_string()))?;
+                Ok(DynamicImage::from(image_buffer))
+            }
+            CaptureRegion::AllDisplays => {
+                let mut captures = Vec::new();
+                for display_info in display_vec.iter() {
+                    let image_buffer = Screen::new(display_info).capture().map_err(|err| CaptureError::Backend(err.to_string()))?;
+                    captures.push((Display::from(display_info), image_buffer));
+                }
+                Ok(stitch_displays(captures))
+            }
+            CaptureRegion::Area(x, y, width, height) => {
+                let displays: Vec<Display> = display_vec.iter().map(Display::from).collect();
+                let display = get_area_display(&displays, x, y, width, height).ok_or(CaptureError::InvalidArea)?;
+                let display_info = &display_vec[display];
+                let scale_factor = display_info.scale_factor;
+                let image_buffer = Screen::new(display_info).capture_area(
+                    (x as f32 / scale_factor) as i32 - display_info.x,
+                    (y as f32 / scale_factor) as i32 - display_info.y,
+                    (width as f32 / scale_factor) as u32,
+                    (height as f32 / scale_factor) as u32
+                ).map_err(|err| CaptureError::Backend(err.to_string()))?;
+                Ok(DynamicImage::from(image_buffer))
+            }
+        };
+    }
+}
+
+
+// Serves the PNG files of a directory, sorted by name, as displays laid out side by side
+pub struct FileCaptureSource {
+    dir: PathBuf,
+}
+
+impl FileCaptureSource {
+    pub fn new(dir: PathBuf) -> Self {
+        FileCaptureSource {
+            dir: dir
+        }
+    }
+
+    fn list_files(&self) -> Result<Vec<PathBuf>, CaptureError> {
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.dir)
+            .map_err(|err| CaptureError::Io(err.to_string()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().map_or(false, |extension| extension.eq_ignore_ascii_case("png")))
+            .collect();
+        files.sort();
+
+        return Ok(files);
+    }
+
+    fn load(&self, file: &PathBuf) -> Result<RgbaImage, CaptureError> {
+        let image = image::open(file).map_err(|err| CaptureError::Io(err.to_string()))?;
+        return Ok(image.to_rgba8());
+    }
+}
+
+impl CaptureSource for FileCaptureSource {
+    fn list_displays(&self) -> Result<Vec<Display>, CaptureError> {
+        let mut displays = Vec::new();
+        let mut x = 0;
+
+        for file in self.list_files()?.iter() {
+            let (width, height) = image::image_dimensions(file).map_err(|err| CaptureError::Io(err.to_string()))?;
+            displays.push(Display {
+                x: x,
+                y: 0,
+                width: width,
+                height: height,
+                scale_factor: 1.
+            });
+            x += width as i32;
+        }
+
+        return Ok(displays);
+    }
+
+    fn capture(&self, region: CaptureRegion) -> Result<DynamicImage, CaptureError> {
+        let files = self.list_files()?;
+        if files.is_empty() {
+            return Err(CaptureError::NoDisplays);
+        }
+
+        let all_displays = || -> Result<DynamicImage, CaptureError> {
+            let mut captures = Vec::new();
+            for (display, file) in self.list_displays()?.into_iter().zip(files.iter()) {
+                captures.push((display, self.load(file)?));
+            }
+            return Ok(stitch_displays(captures));
+        };
+
+        return match region {
+            CaptureRegion::Display(display) => {
+                let file = files.get(display).ok_or(CaptureError::InvalidDisplay(display))?;
+                Ok(DynamicImage::ImageRgba8(self.load(file)?))
+            }
+            CaptureRegion::AllDisplays => all_displays(),
+            CaptureRegion::Area(x, y, width, height) => {
+                let desktop = all_displays()?;
+                crop_image(&desktop, x as i64, y as i64, width, height).ok_or(CaptureError::InvalidArea)
+            }
+        };
+    }
+}
+
+
+// Places each display capture at its offset in the virtual desktop,
+// areas not covered by any display are left transparent
+pub fn stitch_displays(captures: Vec<(Display, RgbaImage)>) -> DynamicImage {
+    let offsets: Vec<(i64, i64)> = captures.iter()
+        .map(|(display, _)| (
+            (display.x as f32 * display.scale_factor) as i64,
+            (display.y as f32 * display.scale_factor) as i64
+        ))
+        .collect();
+
+    let min_x = offsets.iter().map(|offset| offset.0).min().unwrap_or(0);
+    let min_y = offsets.iter().map(|offset| offset.1).min().unwrap_or(0);
+
+    let mut width = 0;
+    let mut height = 0;
+    for ((_, capture), offset) in captures.iter().zip(offsets.iter()) {
+        width = width.max(offset.0 - min_x + capture.width() as i64);
+        height = height.max(offset.1 - min_y + capture.height() as i64);
+    }
+
+    let mut desktop = RgbaImage::new(width as u32, height as u32);
+    for ((_, capture), offset) in captures.iter().zip(offsets.iter()) {
+        replace(&mut desktop, capture, offset.0 - min_x, offset.1 - min_y);
+    }
+
+    return DynamicImage::ImageRgba8(desktop);
+}
+
+
+#[derive(PartialEq, Clone)]
+pub struct WindowInfo {
+    pub id: u32,
+    pub title: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    // Decoration sizes (left, right, top, bottom) added by the window manager
+    pub frame_extents: [u32; 4],
+}
+
+impl WindowInfo {
+    // Returns (x, y, width, height) of the area to capture, in screen pixels
+    pub fn get_area(&self, decorations: bool) -> (i32, i32, u32, u32) {
+        if decorations {
+            let [left, right, top, bottom] = self.frame_extents;
+            return (self.x - left as i32, self.y - top as i32, self.width + left + right, self.height + top + bottom);
+        }
+        return (self.x, self.y, self.width, self.height);
+    }
+}
+
+pub fn get_window(id: u32) -> Option<WindowInfo> {
+    return get_windows().into_iter().find(|window| window.id == id);
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_windows() -> Vec<WindowInfo> {
+    return x11_get_windows().unwrap_or_default();
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn get_windows() -> Vec<WindowInfo> {
+    return Vec::new();
+}
+
+#[cfg(target_os = "linux")]
+fn x11_get_atom(conn: &xcb::Connection, name: &str) -> xcb::Result<xcb::x::Atom> {
+    let cookie = conn.send_request(&xcb::x::InternAtom {
+        only_if_exists: true,
+        name: name.as_bytes(),
+    });
+    return Ok(conn.wait_for_reply(cookie)?.atom());
+}
+
+#[cfg(target_os = "linux")]
+fn x11_get_property<T: xcb::x::PropEl + Clone>(conn: &xcb::Connection, window: xcb::x::Window, property: xcb::x::Atom, length: u32) -> xcb::Result<Vec<T>> {
+    if property == xcb::x::ATOM_NONE {
+        return Ok(Vec::new());
+    }
+
+    let cookie = conn.send_request(&xcb::x::GetProperty {
+        delete: false,
+        window: window,
+        property: property,
+        r#type: xcb::x::ATOM_ANY,
+        long_offset: 0,
+        long_length: length,
+    });
+    let reply = conn.wait_for_reply(cookie)?;
+
+    return Ok(reply.value::<T>().to_vec());
+}
+
+#[cfg(target_os = "linux")]
+fn x11_get_windows() -> xcb::Result<Vec<WindowInfo>> {
+    use xcb::x;
+    use xcb::Xid;
+
+    let (conn, screen_index) = xcb::Connection::connect(None)?;
+    let root = match conn.get_setup().roots().nth(screen_index as usize) {
+        Some(screen) => screen.root(),
+        None => return Ok(Vec::new()),
+    };
+
+    let client_list_atom = x11_get_atom(&conn, "_NET_CLIENT_LIST")?;
+    let wm_name_atom = x11_get_atom(&conn, "_NET_WM_NAME")?;
+    let frame_extents_atom = x11_get_atom(&conn, "_NET_FRAME_EXTENTS")?;
+
+    // Prefer the window manager client list, fall back to the mapped children
+    // of the root window when no EWMH window manager is running (e.g. Xvfb)
+    let mut clients: Vec<x::Window> = x11_get_property(&conn, root, client_list_atom, 1024)?;
+    if clients.is_empty() {
+        let cookie = conn.send_request(&x::QueryTree { window: root });
+        clients = conn.wait_for_reply(cookie)?.children().to_vec();
+    }
+
+    let mut windows = Vec::new();
+
+    for client in clients {
+        let cookie = conn.send_request(&x::GetWindowAttributes { window: client });
+        let attributes = conn.wait_for_reply(cookie)?;
+        if attributes.map_state() != x::MapState::Viewable || attributes.override_redirect() {
+            continue;
+        }
+
+        let cookie = conn.send_request(&x::GetGeometry { drawable: x::Drawable::Window(client) });
+        let geometry = conn.wait_for_reply(cookie)?;
+        if geometry.width() == 0 || geometry.height() == 0 {
+            continue;
+        }
+
+        let cookie = conn.send_request(&x::TranslateCoordinates {
+            src_window: client,
+            dst_window: root,
+            src_x: 0,
+            src_y: 0,
+        });
+        let position = conn.wait_for_reply(cookie)?;
+
+        let mut title_bytes: Vec<u8> = x11_get_property(&conn, client, wm_name_atom, 1024)?;
+        if title_bytes.is_empty() {
+            title_bytes = x11_get_property(&conn, client, x::ATOM_WM_NAME, 1024)?;
+        }
+        let mut title = String::from_utf8_lossy(&title_bytes).to_string();
+        if title.is_empty() {
+            title = format!("Window 0x{:x}", client.resource_id());
+        }
+
+        let extents: Vec<u32> = x11_get_property(&conn, client, frame_extents_atom, 4)?;
+        let mut frame_extents = [0; 4];
+        if extents.len() == 4 {
+            frame_extents.copy_from_slice(&extents);
+        }
+
+        windows.push(WindowInfo {
+            id: client.resource_id(),
+            title: title,
+            x: position.dst_x() as i32,
+            y: position.dst_y() as i32,
+            width: geometry.width() as u32,
+            height: geometry.height() as u32,
+            frame_extents: frame_extents,
+        });
+    }
+
+    return Ok(windows);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgba};
+    use std::path::Path;
+    use std::sync::Mutex;
+
+    // The tests share the SNAP_RUST_CAPTURE_DIR variable, so they take turns
+    static CAPTURE_DIR_LOCK: Mutex<()> = Mutex::new(());
+
+    // Fills a fresh directory with one solid color PNG per (width, height, color) and points
+    // SNAP_RUST_CAPTURE_DIR at it
+    fn set_capture_dir(name: &str, displays: &[(u32, u32, [u8; 4])]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("snap_rust_{}_{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+
+        for (index, (width, height, color)) in displays.iter().enumerate() {
+            RgbaImage::from_pixel(*width, *height, Rgba(*color)).save(dir.join(format!("display_{}.png", index))).unwrap();
+        }
+        env::set_var("SNAP_RUST_CAPTURE_DIR", &dir);
+
+        return dir;
+    }
+
+    fn remove_capture_dir(dir: &Path) {
+        env::remove_var("SNAP_RUST_CAPTURE_DIR");
+        fs::remove_dir_all(dir).ok();
+    }
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    #[test]
+    fn captures_one_display() {
+        let _lock = CAPTURE_DIR_LOCK.lock().unwrap();
+        let dir = set_capture_dir("one_display", &[(40, 30, RED), (20, 10, BLUE)]);
+
+        let source = get_capture_source();
+        let displays = source.list_displays().unwrap();
+        assert_eq!(displays.len(), 2);
+        assert_eq!(displays[1], Display { x: 40, y: 0, width: 20, height: 10, scale_factor: 1. });
+
+        let capture = source.capture(CaptureRegion::Display(1)).unwrap();
+        assert_eq!(capture.dimensions(), (20, 10));
+        assert_eq!(capture.get_pixel(5, 5).0, BLUE);
+
+        remove_capture_dir(&dir);
+    }
+
+    #[test]
+    fn stitches_all_displays_with_transparent_gaps() {
+        let _lock = CAPTURE_DIR_LOCK.lock().unwrap();
+        let dir = set_capture_dir("all_displays", &[(40, 30, RED), (20, 10, BLUE)]);
+
+        let capture = get_capture_source().capture(CaptureRegion::AllDisplays).unwrap();
+        assert_eq!(capture.dimensions(), (60, 30));
+        assert_eq!(capture.get_pixel(39, 29).0, RED);
+        assert_eq!(capture.get_pixel(40, 9).0, BLUE);
+        // Below the shorter display
+        assert_eq!(capture.get_pixel(50, 20).0, [0, 0, 0, 0]);
+
+        remove_capture_dir(&dir);
+    }
+
+    #[test]
+    fn crops_an_area_of_the_desktop() {
+        let _lock = CAPTURE_DIR_LOCK.lock().unwrap();
+        let dir = set_capture_dir("area", &[(40, 30, RED), (20, 10, BLUE)]);
+
+        let source = get_capture_source();
+        let capture = source.capture(CaptureRegion::Area(30, 0, 20, 5)).unwrap();
+        assert_eq!(capture.dimensions(), (20, 5));
+        assert_eq!(capture.get_pixel(9, 0).0, RED);
+        assert_eq!(capture.get_pixel(10, 0).0, BLUE);
+
+        // Clamped to the desktop, and an error when nothing is left
+        assert_eq!(source.capture(CaptureRegion::Area(50, 0, 100, 100)).unwrap().dimensions(), (10, 30));
+        assert_eq!(source.capture(CaptureRegion::Area(100, 100, 10, 10)).unwrap_err(), CaptureError::InvalidArea);
+
+        remove_capture_dir(&dir);
+    }
+
+    #[test]
+    fn reports_missing_displays() {
+        let _lock = CAPTURE_DIR_LOCK.lock().unwrap();
+        let dir = set_capture_dir("invalid_display", &[(40, 30, RED)]);
+        assert_eq!(get_capture_source().capture(CaptureRegion::Display(1)).unwrap_err(), CaptureError::InvalidDisplay(1));
+        remove_capture_dir(&dir);
+
+        let dir = set_capture_dir("no_displays", &[]);
+        let source = get_capture_source();
+        assert!(source.list_displays().unwrap().is_empty());
+        assert_eq!(source.capture(CaptureRegion::Display(0)).unwrap_err(), CaptureError::NoDisplays);
+        assert_eq!(source.capture(CaptureRegion::AllDisplays).unwrap_err(), CaptureError::NoDisplays);
+        remove_capture_dir(&dir);
+    }
+
+    #[test]
+    fn picks_the_scale_factor_of_the_captured_display() {
+        let displays = [
+            Display { x: 0, y: 0, width: 100, height: 100, scale_factor: 1. },
+            Display { x: 100, y: 0, width: 100, height: 100, scale_factor: 2. },
+        ];
+        assert_eq!(get_capture_scale_factor(&displays, CaptureRegion::Display(1)), 2.);
+        assert_eq!(get_capture_scale_factor(&displays, CaptureRegion::AllDisplays), 2.);
+        // The area is in screen pixels, its center is at x = 125 logical pixels on the second display
+        assert_eq!(get_capture_scale_factor(&displays, CaptureRegion::Area(240, 0, 20, 20)), 2.);
+        assert_eq!(get_capture_scale_factor(&displays, CaptureRegion::Area(10, 10, 20, 20)), 1.);
+    }
+
+    #[test]
+    fn clamps_the_region_to_the_image() {
+        // The image is shown at half size with its top left corner at (10, 20)
+        let view = ViewTransform { origin: Pos2::new(10., 20.), scale: 0.5 };
+
+        assert_eq!(get_region_rect(Pos2::new(20., 30.), Pos2::new(40., 35.), &view, [100, 50]), (20, 20, 40, 10));
+        // Dragging up and to the left gives the same rectangle
+        assert_eq!(get_region_rect(Pos2::new(40., 35.), Pos2::new(20., 30.), &view, [100, 50]), (20, 20, 40, 10));
+        // Ending outside the image stops at its borders
+        assert_eq!(get_region_rect(Pos2::new(20., 30.), Pos2::new(1000., 1000.), &view, [100, 50]), (20, 20, 80, 30));
+        assert_eq!(get_region_rect(Pos2::new(20., 30.), Pos2::new(0., 0.), &view, [100, 50]), (0, 0, 20, 20));
+        // Nothing left when the whole drag is outside
+        assert_eq!(get_region_rect(Pos2::new(200., 0.), Pos2::new(300., 10.), &view, [100, 50]), (100, 0, 0, 0));
+    }
+
+
+    #[test]
+    fn finds_the_display_of_an_area_in_screen_pixels() {
+        // A 2x display 200 screen pixels wide, left of a 1x one
+        let displays = [
+            Display { x: 0, y: 0, width: 100, height: 100, scale_factor: 2. },
+            Display { x: 200, y: 0, width: 100, height: 100, scale_factor: 1. },
+        ];
+        assert_eq!(get_area_display(&displays, 150, 40, 20, 20), Some(0));
+        assert_eq!(get_area_display(&displays, 240, 40, 20, 20), Some(1));
+        assert_eq!(get_area_display(&displays, 500, 500, 20, 20), None);
+    }
+
+    #[test]
+    fn adds_the_decorations_to_the_window_area() {
+        let window = WindowInfo {
+            id: 1,
+            title: String::from("Window"),
+            x: 100,
+            y: 50,
+            width: 300,
+            height: 200,
+            frame_extents: [2, 3, 20, 4],
+        };
+        assert_eq!(window.get_area(false), (100, 50, 300, 200));
+        assert_eq!(window.get_area(true), (98, 30, 305, 224));
+    }
+
+    // Needs an X server, it is skipped when DISPLAY is not set (run it with xvfb-run cargo test)
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn lists_x11_windows() {
+        use xcb::x;
+        use xcb::Xid;
+        use std::time::{Duration, Instant};
+
+        if env::var_os("DISPLAY").is_none() {
+            return;
+        }
+
+        let (conn, screen_index) = xcb::Connection::connect(None).unwrap();
+        let screen = conn.get_setup().roots().nth(screen_index as usize).unwrap();
+        let window: x::Window = conn.generate_id();
+        conn.send_request(&x::CreateWindow {
+            depth: x::COPY_FROM_PARENT as u8,
+            wid: window,
+            parent: screen.root(),
+            x: 30,
+            y: 40,
+            width: 120,
+            height: 80,
+            border_width: 0,
+            class: x::WindowClass::InputOutput,
+            visual: screen.root_visual(),
+            value_list: &[],
+        });
+        conn.send_request(&x::ChangeProperty {
+            mode: x::PropMode::Replace,
+            window: window,
+            property: x::ATOM_WM_NAME,
+            r#type: x::ATOM_STRING,
+            data: b"snap_rust test window",
+        });
+        conn.send_and_check_request(&x::MapWindow { window: window }).unwrap();
+
+        // A window manager may take a moment to map and list the window
+        let start = Instant::now();
+        let mut listed = None;
+        while listed.is_none() && start.elapsed() < Duration::from_secs(5) {
+            listed = get_window(window.resource_id());
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        let listed = listed.expect("The test window is not listed");
+        assert_eq!(listed.title, "snap_rust test window");
+        assert_eq!((listed.width, listed.height), (120, 80));
+        assert!(get_windows().iter().any(|window| window.id == listed.id));
+    }
+}
@@ -0,0 +1,230 @@
+use snap_rust::capture_utils::{get_capture_source, CaptureRegion};
+use snap_rust::export_utils::get_default_file_name;
+use snap_rust::document::Document;
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+const USAGE: &str = "Usage:
+    snap_rust                  Start the graphical interface
+    snap_rust capture [OPTIONS]
+
+Capture options:
+    --display <N>          Display to capture (default 0)
+    --all                  Capture all the displays as one image
+    --delay <SECONDS>      Wait before capturing
+    --region <X,Y,W,H>     Keep only this area of the capture, in pixels
+    --output <FILE>        Output file, the format is picked from the extension
+    --stdout               Write the capture as PNG to the standard output instead of a file";
+
+pub struct CaptureArgs {
+    pub display: Option<usize>,
+    pub delay: f64,
+    pub region: Option<(i64, i64, u32, u32)>,
+    pub output: Option<PathBuf>,
+    pub stdout: bool,
+}
+
+impl Default for CaptureArgs {
+    fn default() -> Self {
+        CaptureArgs {
+            display: Some(0),
+            delay: 0.,
+            region: None,
+            output: None,
+            stdout: false,
+        }
+    }
+}
+
+// Runs the command line interface and returns the process exit code
+pub fn run(args: &[String]) -> i32 {
+    match args[0].as_str() {
+        "capture" => {
+            match parse_capture_args(&args[1..]) {
+                Ok(capture_args) => match capture(&capture_args) {
+                    Ok(_) => 0,
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        1
+                    }
+                },
+                Err(err) => {
+                    eprintln!("{}\n\n{}", err, USAGE);
+                    2
+                }
+            }
+        }
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            0
+        }
+        command => {
+            eprintln!("Unknown command: {}\n\n{}", command, USAGE);
+            2
+        }
+    }
+}
+
+pub fn parse_capture_args(args: &[String]) -> Result<CaptureArgs, String> {
+    let mut capture_args = CaptureArgs::default();
+    let mut args_iterator = args.iter();
+
+    while let Some(arg) = args_iterator.next() {
+        let mut value = || args_iterator.next().ok_or(format!("Missing value for {}", arg));
+
+        match arg.as_str() {
+            "--display" => {
+                let display = value()?;
+                capture_args.display = Some(display.parse().map_err(|_| format!("Invalid display: {}", display))?);
+            }
+            "--all" => capture_args.display = None,
+            "--delay" => {
+                let delay = value()?;
+                capture_args.delay = delay.parse().map_err(|_| format!("Invalid delay: {}", delay))?;
+                if !capture_args.delay.is_finite() || capture_args.delay < 0. {
+                    return Err(format!("Invalid delay: {}", delay));
+                }
+            }
+            "--region" => {
+                let region = value()?;
+                capture_args.region = Some(parse_region(region).ok_or(format!("Invalid region: {}", region))?);
+            }
+            "--output" => capture_args.output = Some(PathBuf::from(value()?)),
+            "--stdout" => capture_args.stdout = true,
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+
+    if capture_args.stdout && capture_args.output.is_some() {
+        return Err("--stdout and --output cannot be used together".to_string());
+    }
+
+    return Ok(capture_args);
+}
+
+fn parse_region(region: &str) -> Option<(i64, i64, u32, u32)> {
+    let values: Vec<&str> = region.split(',').map(|value| value.trim()).collect();
+    if values.len() != 4 {
+        return None;
+    }
+
+    return Some((
+        values[0].parse().ok()?,
+        values[1].parse().ok()?,
+        values[2].parse().ok()?,
+        values[3].parse().ok()?
+    ));
+}
+
+pub fn capture(capture_args: &CaptureArgs) -> Result<(), String> {
+    thread::sleep(Duration::from_secs_f64(capture_args.delay));
+
+    let region = match capture_args.display {
+        Some(display) => CaptureRegion::Display(display),
+        None => CaptureRegion::AllDisplays,
+    };
+
+    let snapshot = get_capture_source().capture(region).map_err(|err| err.to_string())?;
+    let mut document = Document::new(snapshot);
+
+    if let Some((x, y, width, height)) = capture_args.region {
+        if !document.crop(x, y, width, height) {
+            return Err("The region is outside of the capture".to_string());
+        }
+    }
+
+    if capture_args.stdout {
+        let bytes = document.encode_png().map_err(|err| err.to_string())?;
+        io::stdout().write_all(&bytes).map_err(|err| err.to_string())?;
+        return Ok(());
+    }
+
+    let path = match capture_args.output.as_ref() {
+        Some(path) => path.clone(),
+        None => PathBuf::from(get_default_file_name() + ".png"),
+    };
+    document.save(&path).map_err(|err| err.to_string())?;
+
+    return Ok(());
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgba, RgbaImage};
+    use std::env;
+    use std::fs;
+    use std::time::Instant;
+
+    fn parse(args: &[&str]) -> Result<CaptureArgs, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        return parse_capture_args(&args);
+    }
+
+    #[test]
+    fn parses_capture_options() {
+        let capture_args = parse(&[]).unwrap();
+        assert_eq!(capture_args.display, Some(0));
+        assert_eq!(capture_args.delay, 0.);
+        assert!(capture_args.region.is_none() && capture_args.output.is_none() && !capture_args.stdout);
+
+        let capture_args = parse(&["--display", "2", "--delay", "1.5", "--region", "10, -20,300,400", "--output", "shot.png"]).unwrap();
+        assert_eq!(capture_args.display, Some(2));
+        assert_eq!(capture_args.delay, 1.5);
+        assert_eq!(capture_args.region, Some((10, -20, 300, 400)));
+        assert_eq!(capture_args.output, Some(PathBuf::from("shot.png")));
+
+        let capture_args = parse(&["--all", "--stdout"]).unwrap();
+        assert_eq!(capture_args.display, None);
+        assert!(capture_args.stdout);
+    }
+
+    #[test]
+    fn rejects_invalid_capture_options() {
+        for args in [
+            &["--display"][..],
+            &["--display", "-1"],
+            &["--delay", "-1"],
+            &["--delay", "nan"],
+            &["--delay", "inf"],
+            &["--region", "1,2,3"],
+            &["--region", "1,2,-3,4"],
+            &["--stdout", "--output", "shot.png"],
+            &["--verbose"],
+        ] {
+            assert!(parse(args).is_err(), "{:?} should be rejected", args);
+        }
+    }
+
+    #[test]
+    fn captures_a_region_after_the_delay() {
+        let dir = env::temp_dir().join(format!("snap_rust_cli_{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        RgbaImage::from_fn(40, 30, |x, _| if x < 20 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) })
+            .save(dir.join("display_0.png"))
+            .unwrap();
+        env::set_var("SNAP_RUST_CAPTURE_DIR", &dir);
+
+        let output = dir.join("shot.png");
+        let capture_args = parse(&["--delay", "0.2", "--region", "15,5,10,10", "--output", output.to_str().unwrap()]).unwrap();
+        let start = Instant::now();
+        capture(&capture_args).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(200));
+
+        let shot = image::open(&output).unwrap();
+        assert_eq!(shot.dimensions(), (10, 10));
+        assert_eq!(shot.get_pixel(4, 0).0, [255, 0, 0, 255]);
+        assert_eq!(shot.get_pixel(5, 0).0, [0, 0, 255, 255]);
+
+        let capture_args = parse(&["--region", "100,100,10,10", "--output", output.to_str().unwrap()]).unwrap();
+        assert!(capture(&capture_args).is_err());
+
+        env::remove_var("SNAP_RUST_CAPTURE_DIR");
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,505 @@
+use image::{DynamicImage, ImageResult};
+use image::imageops::FilterType;
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::annotation::Annotation;
+use crate::tool_utils::{crop_image, redact_image, RedactStyle, Transform};
+use crate::export_utils::{save_image, encode_png};
+use crate::adjust_utils::{adjust_image, Adjustments};
+use egui::FontFamily;
+use rusttype::Font;
+
+// One step of an edit. Annotation changes only keep the objects involved, the capture is only
+// kept whole by the edits that change its pixels.
+#[derive(Clone)]
+pub enum Change {
+    Insert(usize, Annotation),
+    Remove(usize, Annotation),
+    Replace(usize, Annotation, Annotation),
+    // Before and after
+    Background(Arc<DynamicImage>, Arc<DynamicImage>),
+    Annotations(Vec<Annotation>, Vec<Annotation>),
+    Adjustments(Adjustments, Adjustments),
+    ScaleFactor(f32, f32),
+    NextStep(u32, u32),
+}
+
+// A captured image being edited: the capture, the annotations drawn on top of it and the
+// undo/redo history of both. Annotations are only flattened into the image on export.
+pub struct Document {
+    // The capture, cropped and redacted but without annotations
+    background: Arc<DynamicImage>,
+    pub annotations: Vec<Annotation>,
+    adjustments: Adjustments,
+    // The background with the adjustments applied, and the background and adjustments it was made from
+    adjusted: Arc<DynamicImage>,
+    adjusted_source: (Arc<DynamicImage>, Adjustments),
+    // Number of the next step marker
+    pub next_step: u32,
+    changes_undo: VecDeque<Vec<Change>>,
+    changes_redo: VecDeque<Vec<Change>>,
+    // Pixels of the capture per point of the display it was taken on, 1 once it is downscaled to
+    // the logical resolution
+    scale_factor: f32,
+    // Counts the changes applied, to know when something computed from the document is outdated
+    revision: u64,
+}
+
+impl Document {
+    pub fn new(snapshot: DynamicImage) -> Self {
+        let background = Arc::new(snapshot);
+
+        Document {
+            adjusted: background.clone(),
+            adjusted_source: (background.clone(), Adjustments::default()),
+            background: background,
+            annotations: Vec::new(),
+            adjustments: Adjustments::default(),
+            next_step: 1,
+            changes_undo: VecDeque::new(),
+            changes_redo: VecDeque::new(),
+            scale_factor: 1.,
+            revision: 0
+        }
+    }
+
+    pub fn background(&self) -> &DynamicImage {
+        return &self.background;
+    }
+
+    // The background as it is shown and exported
+    pub fn adjusted(&self) -> &DynamicImage {
+        return &self.adjusted;
+    }
+
+    pub fn adjustments(&self) -> Adjustments {
+        return self.adjustments;
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        return self.scale_factor;
+    }
+
+    // Scale factor of the display the capture was taken on, it is not part of the undo history
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        self.scale_factor = scale_factor;
+    }
+
+    pub fn revision(&self) -> u64 {
+        return self.revision;
+    }
+
+    // Runs the adjustments again after the background or the adjustments changed
+    fn update_adjusted(&mut self) {
+        if Arc::ptr_eq(&self.adjusted_source.0, &self.background) && self.adjusted_source.1 == self.adjustments {
+            return;
+        }
+        self.adjusted = if self.adjustments.is_identity() {
+            self.background.clone()
+        } else {
+            Arc::new(adjust_image(&self.background, &self.adjustments, 1.))
+        };
+        self.adjusted_source = (self.background.clone(), self.adjustments);
+    }
+
+    fn apply(&mut self, change: &Change, undo: bool) {
+        self.revision += 1;
+        match (change, undo) {
+            (Change::Insert(index, annotation), false) | (Change::Remove(index, annotation), true) => {
+                self.annotations.insert(*index, annotation.clone());
+            }
+            (Change::Insert(index, _), true) | (Change::Remove(index, _), false) => {
+                self.annotations.remove(*index);
+            }
+            (Change::Replace(index, before, after), _) => {
+                self.annotations[*index] = if undo { before.clone() } else { after.clone() };
+            }
+            (Change::Background(before, after), _) => {
+                self.background = if undo { before.clone() } else { after.clone() };
+            }
+            (Change::Annotations(before, after), _) => {
+                self.annotations = if undo { before.clone() } else { after.clone() };
+            }
+            (Change::Adjustments(before, after), _) => {
+                self.adjustments = if undo { *before } else { *after };
+            }
+            (Change::ScaleFactor(before, after), _) => {
+                self.scale_factor = if undo { *before } else { *after };
+            }
+            (Change::NextStep(before, after), _) => {
+                self.next_step = if undo { *before } else { *after };
+            }
+        }
+    }
+
+    // Applies the changes in order and records them as a single undo step
+    pub fn edit(&mut self, changes: Vec<Change>) {
+        if changes.is_empty() {
+            return;
+        }
+        for change in changes.iter() {
+            self.apply(change, false);
+        }
+        self.changes_undo.push_back(changes);
+        self.changes_redo.clear();
+        self.update_adjusted();
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(changes) = self.changes_undo.pop_back() {
+            for change in changes.iter().rev() {
+                self.apply(change, true);
+            }
+            self.changes_redo.push_front(changes);
+            self.update_adjusted();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(changes) = self.changes_redo.pop_front() {
+            for change in changes.iter() {
+                self.apply(change, false);
+            }
+            self.changes_undo.push_back(changes);
+            self.update_adjusted();
+        }
+    }
+
+    pub fn add(&mut self, annotation: Annotation) {
+        self.edit(vec![Change::Insert(self.annotations.len(), annotation)]);
+    }
+
+    pub fn replace(&mut self, index: usize, annotation: Annotation) {
+        self.edit(vec![Change::Replace(index, self.annotations[index].clone(), annotation)]);
+    }
+
+    // Changes the annotation as part of the last undo step instead of a new one, if that step ends
+    // by replacing the same annotation. Returns false otherwise.
+    pub fn amend(&mut self, index: usize, annotation: Annotation) -> bool {
+        if !self.changes_redo.is_empty() {
+            return false;
+        }
+        if let Some(Change::Replace(last_index, _, after)) = self.changes_undo.back_mut().and_then(|changes| changes.last_mut()) {
+            if *last_index == index {
+                *after = annotation.clone();
+                self.annotations[index] = annotation;
+                self.revision += 1;
+                return true;
+            }
+        }
+        return false;
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.edit(vec![Change::Remove(index, self.annotations[index].clone())]);
+    }
+
+    // Moves an annotation in the drawing order, the last one is drawn on top of the others
+    pub fn reorder(&mut self, index: usize, new_index: usize) {
+        if index == new_index {
+            return;
+        }
+        let annotation = self.annotations[index].clone();
+        self.edit(vec![
+            Change::Remove(index, annotation.clone()),
+            Change::Insert(new_index, annotation)
+        ]);
+    }
+
+    // Adds a step marker numbered with next_step and advances the counter
+    pub fn add_step_marker(&mut self, center: (f32, f32), radius: f32, fonts: Vec<Font<'static>>, family: FontFamily, color: [u8; 4]) {
+        let step = Annotation::Step {
+            center: center,
+            radius: radius,
+            number: self.next_step,
+            fonts: fonts,
+            family: family,
+            color: color
+        };
+        self.edit(vec![
+            Change::Insert(self.annotations.len(), step),
+            Change::NextStep(self.next_step, self.next_step + 1)
+        ]);
+    }
+
+    pub fn reset_steps(&mut self) {
+        if self.next_step != 1 {
+            self.edit(vec![Change::NextStep(self.next_step, 1)]);
+        }
+    }
+
+    // The area of the capture is replaced, only the undo history keeps the original pixels.
+    // Annotations stay on top of it.
+    pub fn redact(&mut self, x: u32, y: u32, width: u32, height: u32, style: RedactStyle, strength: u32, color: [u8; 4]) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let mut redacted = self.background.as_ref().clone();
+        redact_image(&mut redacted, x, y, width, height, style, strength, color);
+        self.edit(vec![Change::Background(self.background.clone(), Arc::new(redacted))]);
+    }
+
+    // Returns false if the rectangle does not overlap the capture
+    pub fn crop(&mut self, x: i64, y: i64, width: u32, height: u32) -> bool {
+        match crop_image(&self.background, x, y, width, height) {
+            Some(cropped_image) => {
+                // crop_image clamps the rectangle, move the annotations by what was actually cut
+                let offset = (-(x.clamp(0, self.background.width() as i64) as f32), -(y.clamp(0, self.background.height() as i64) as f32));
+                let mut annotations = self.annotations.clone();
+                for annotation in annotations.iter_mut() {
+                    annotation.translate(offset);
+                }
+
+                self.edit(vec![
+                    Change::Background(self.background.clone(), Arc::new(cropped_image)),
+                    Change::Annotations(self.annotations.clone(), annotations)
+                ]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Scales the capture and the annotations on it to width x height pixels
+    pub fn resize(&mut self, width: u32, height: u32, filter: FilterType) {
+        if width == 0 || height == 0 || (width == self.background.width() && height == self.background.height()) {
+            return;
+        }
+
+        let scale = (width as f32 / self.background.width() as f32, height as f32 / self.background.height() as f32);
+        let mut annotations = self.annotations.clone();
+        for annotation in annotations.iter_mut() {
+            annotation.scale(scale);
+        }
+
+        self.edit(vec![
+            Change::Background(self.background.clone(), Arc::new(self.background.resize_exact(width, height, filter))),
+            Change::Annotations(self.annotations.clone(), annotations),
+            Change::ScaleFactor(self.scale_factor, self.scale_factor * scale.0)
+        ]);
+    }
+
+    // The adjustments are kept apart from the capture, so they can be changed or undone later
+    pub fn adjust(&mut self, adjustments: Adjustments) {
+        if adjustments != self.adjustments {
+            self.edit(vec![Change::Adjustments(self.adjustments, adjustments)]);
+        }
+    }
+
+    pub fn transform(&mut self, transform: Transform) {
+        let size = (self.background.width() as f32, self.background.height() as f32);
+        let mut annotations = self.annotations.clone();
+        for annotation in annotations.iter_mut() {
+            annotation.transform(transform, size);
+        }
+
+        self.edit(vec![
+            Change::Background(self.background.clone(), Arc::new(transform.apply_to_image(&self.background))),
+            Change::Annotations(self.annotations.clone(), annotations)
+        ]);
+    }
+
+    // The adjusted capture with the annotations drawn on it
+    pub fn flatten(&self) -> DynamicImage {
+        let mut image = self.adjusted.as_ref().clone();
+        for annotation in self.annotations.iter() {
+            annotation.rasterize(&mut image, &self.adjusted);
+        }
+        return image;
+    }
+
+    pub fn save(&self, path: &Path) -> ImageResult<()> {
+        return save_image(path, &self.flatten());
+    }
+
+    pub fn encode_png(&self) -> ImageResult<Vec<u8>> {
+        return encode_png(&self.flatten());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn resizes_with_the_annotations_and_scale_factor() {
+        let mut document = Document::new(DynamicImage::ImageRgba8(RgbaImage::new(200, 100)));
+        document.set_scale_factor(2.);
+        document.add(Annotation::Rectangle { start: (10., 20.), end: (110., 60.), color: [255, 0, 0, 255], size: 4, filled: false, angle: 0. });
+
+        document.resize(100, 50, FilterType::Nearest);
+        assert_eq!((document.background().width(), document.background().height()), (100, 50));
+        assert_eq!(document.scale_factor(), 1.);
+        match &document.annotations[0] {
+            Annotation::Rectangle { start, end, .. } => assert_eq!((*start, *end), ((5., 10.), (55., 30.))),
+            _ => unreachable!(),
+        }
+
+        document.undo();
+        assert_eq!((document.background().width(), document.background().height()), (200, 100));
+        assert_eq!(document.scale_factor(), 2.);
+        document.redo();
+        assert_eq!(document.scale_factor(), 1.);
+    }
+
+
+    fn get_step_numbers(document: &Document) -> Vec<u32> {
+        return document.annotations.iter().filter_map(|annotation| match annotation {
+            Annotation::Step { number, .. } => Some(*number),
+            _ => None,
+        }).collect();
+    }
+
+    #[test]
+    fn undoes_step_markers_with_the_counter() {
+        let mut document = Document::new(DynamicImage::ImageRgba8(RgbaImage::new(100, 100)));
+        let add_step = |document: &mut Document| document.add_step_marker((50., 50.), 10., Vec::new(), FontFamily::Proportional, [255, 0, 0, 255]);
+
+        add_step(&mut document);
+        add_step(&mut document);
+        assert_eq!(get_step_numbers(&document), vec![1, 2]);
+        assert_eq!(document.next_step, 3);
+
+        // The marker and the counter are one step
+        document.undo();
+        assert_eq!(get_step_numbers(&document), vec![1]);
+        assert_eq!(document.next_step, 2);
+        document.redo();
+        assert_eq!(get_step_numbers(&document), vec![1, 2]);
+        assert_eq!(document.next_step, 3);
+
+        // Resetting keeps the markers and starts again from 1
+        document.reset_steps();
+        assert_eq!(document.next_step, 1);
+        add_step(&mut document);
+        assert_eq!(get_step_numbers(&document), vec![1, 2, 1]);
+
+        document.undo();
+        document.undo();
+        assert_eq!(get_step_numbers(&document), vec![1, 2]);
+        assert_eq!(document.next_step, 3);
+        document.redo();
+        assert_eq!(document.next_step, 1);
+    }
+
+
+    fn get_rectangle(x: f32, y: f32) -> Annotation {
+        return Annotation::Rectangle { start: (x, y), end: (x + 10., y + 10.), color: [255, 0, 0, 255], size: 1, filled: false, angle: 0. };
+    }
+
+    fn get_positions(document: &Document) -> Vec<(f32, f32)> {
+        return document.annotations.iter().map(|annotation| annotation.bounds().min.into()).collect();
+    }
+
+    #[test]
+    fn undoes_and_redoes_annotation_changes() {
+        let mut document = Document::new(DynamicImage::ImageRgba8(RgbaImage::new(100, 100)));
+        document.add(get_rectangle(0., 0.));
+        document.add(get_rectangle(20., 0.));
+        assert_eq!(get_positions(&document), vec![(0., 0.), (20., 0.)]);
+
+        document.remove(0);
+        assert_eq!(get_positions(&document), vec![(20., 0.)]);
+        document.undo();
+        assert_eq!(get_positions(&document), vec![(0., 0.), (20., 0.)]);
+        document.redo();
+        assert_eq!(get_positions(&document), vec![(20., 0.)]);
+
+        document.replace(0, get_rectangle(40., 0.));
+        assert_eq!(get_positions(&document), vec![(40., 0.)]);
+        document.undo();
+        assert_eq!(get_positions(&document), vec![(20., 0.)]);
+        document.redo();
+        assert_eq!(get_positions(&document), vec![(40., 0.)]);
+
+        // Undo everything, then an edit clears what is left to redo
+        for _ in 0..4 {
+            document.undo();
+        }
+        assert!(document.annotations.is_empty());
+        document.redo();
+        assert_eq!(get_positions(&document), vec![(0., 0.)]);
+        document.add(get_rectangle(60., 0.));
+        document.redo();
+        assert_eq!(get_positions(&document), vec![(0., 0.), (60., 0.)]);
+    }
+
+    #[test]
+    fn amends_the_last_replace() {
+        let mut document = Document::new(DynamicImage::ImageRgba8(RgbaImage::new(100, 100)));
+        document.add(get_rectangle(0., 0.));
+        // The last step is an insert, a new step is needed
+        assert!(!document.amend(0, get_rectangle(5., 0.)));
+
+        document.replace(0, get_rectangle(10., 0.));
+        assert!(document.amend(0, get_rectangle(20., 0.)));
+        assert!(document.amend(0, get_rectangle(30., 0.)));
+        assert_eq!(get_positions(&document), vec![(30., 0.)]);
+        // Only another annotation or a pending redo ends the step
+        document.add(get_rectangle(50., 0.));
+        assert!(!document.amend(0, get_rectangle(40., 0.)));
+
+        document.undo();
+        document.undo();
+        assert_eq!(get_positions(&document), vec![(0., 0.)]);
+        document.redo();
+        assert_eq!(get_positions(&document), vec![(30., 0.)]);
+        assert!(!document.amend(0, get_rectangle(40., 0.)));
+    }
+
+    #[test]
+    fn reorders_in_one_step() {
+        let mut document = Document::new(DynamicImage::ImageRgba8(RgbaImage::new(100, 100)));
+        for x in [0., 20., 40.] {
+            document.add(get_rectangle(x, 0.));
+        }
+
+        document.reorder(0, 2);
+        assert_eq!(get_positions(&document), vec![(20., 0.), (40., 0.), (0., 0.)]);
+        document.reorder(2, 1);
+        assert_eq!(get_positions(&document), vec![(20., 0.), (0., 0.), (40., 0.)]);
+
+        document.undo();
+        assert_eq!(get_positions(&document), vec![(20., 0.), (40., 0.), (0., 0.)]);
+        document.undo();
+        assert_eq!(get_positions(&document), vec![(0., 0.), (20., 0.), (40., 0.)]);
+    }
+
+    #[test]
+    fn moves_the_annotations_by_the_cropped_offset() {
+        let mut document = Document::new(DynamicImage::ImageRgba8(RgbaImage::new(100, 100)));
+        document.add(get_rectangle(50., 50.));
+
+        assert!(document.crop(10, 20, 60, 60));
+        assert_eq!((document.background().width(), document.background().height()), (60, 60));
+        assert_eq!(get_positions(&document), vec![(40., 30.)]);
+
+        // The part of the rectangle outside the capture is not cut
+        assert!(document.crop(-30, -10, 40, 40));
+        assert_eq!((document.background().width(), document.background().height()), (10, 30));
+        assert_eq!(get_positions(&document), vec![(40., 30.)]);
+
+        assert!(!document.crop(100, 100, 10, 10));
+        document.undo();
+        document.undo();
+        assert_eq!((document.background().width(), document.background().height()), (100, 100));
+        assert_eq!(get_positions(&document), vec![(50., 50.)]);
+    }
+
+    #[test]
+    fn flattens_without_changing_the_background() {
+        let mut document = Document::new(DynamicImage::ImageRgba8(RgbaImage::from_pixel(20, 20, Rgba([0, 0, 0, 255]))));
+        document.add(Annotation::Rectangle { start: (5., 5.), end: (15., 15.), color: [255, 0, 0, 255], size: 1, filled: true, angle: 0. });
+
+        let flattened = document.flatten().to_rgba8();
+        assert_eq!(flattened.get_pixel(10, 10), &Rgba([255, 0, 0, 255]));
+        assert_eq!(flattened.get_pixel(1, 1), &Rgba([0, 0, 0, 255]));
+        assert!(document.background().to_rgba8().pixels().all(|pixel| *pixel == Rgba([0, 0, 0, 255])));
+        assert_eq!(document.annotations.len(), 1);
+    }
+}
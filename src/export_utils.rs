@@ -0,0 +1,252 @@
+use image::{imageops, DynamicImage, GrayImage, ImageOutputFormat, ImageResult, Luma, Rgba, RgbaImage};
+use chrono::Local;
+
+use imageproc::filter::gaussian_blur_f32;
+
+use crate::tool_utils::draw_antialiased_segment;
+
+use std::io::Cursor;
+use std::path::Path;
+
+pub fn get_default_file_name() -> String {
+    let formatted_date = Local::now().format("%Y_%m_%d_%H_%M_%S").to_string();
+    return "snapshot_".to_string() + &formatted_date;
+}
+
+// The format is picked from the path extension
+pub fn save_image(path: &Path, image: &DynamicImage) -> ImageResult<()> {
+    return image::save_buffer(
+        path,
+        &image.to_rgba8(),
+        image.width(),
+        image.height(),
+        image::ColorType::Rgba8,
+    );
+}
+
+pub fn encode_png(image: &DynamicImage) -> ImageResult<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    image.write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)?;
+    return Ok(bytes);
+}
+
+// Height of the window title bar added by beautify_image, at scale 1
+const TITLE_BAR_HEIGHT: f32 = 28.;
+
+// Frame drawn around the image when exporting it. Sizes are in pixels.
+#[derive(PartialEq, Clone, Copy)]
+pub struct BeautifySettings {
+    pub enabled: bool,
+    pub padding: u32,
+    pub background: [u8; 3],
+    // The background fades from background to gradient_color, top left to bottom right
+    pub gradient: bool,
+    pub gradient_color: [u8; 3],
+    pub corner_radius: u32,
+    pub shadow_blur: f32,
+    pub shadow_offset: (i32, i32),
+    // From 0 to 1
+    pub shadow_opacity: f32,
+    pub window_chrome: bool,
+}
+
+impl Default for BeautifySettings {
+    fn default() -> Self {
+        BeautifySettings {
+            enabled: false,
+            padding: 64,
+            background: [99, 102, 241],
+            gradient: true,
+            gradient_color: [236, 72, 153],
+            corner_radius: 10,
+            shadow_blur: 16.,
+            shadow_offset: (0, 12),
+            shadow_opacity: 0.5,
+            window_chrome: false,
+        }
+    }
+}
+
+// Puts the image on a background with padding, rounded corners, a drop shadow and optionally a
+// window title bar. scale multiplies all the sizes, for previews made on a smaller copy.
+pub fn beautify_image(img: &DynamicImage, settings: &BeautifySettings, scale: f32) -> DynamicImage {
+    let mut content = img.to_rgba8();
+    if settings.window_chrome {
+        content = add_title_bar(&content, (TITLE_BAR_HEIGHT * scale).round().max(1.) as u32);
+    }
+    round_corners(&mut content, settings.corner_radius as f32 * scale);
+
+    let padding = (settings.padding as f32 * scale).round() as u32;
+    let width = content.width() + padding * 2;
+    let height = content.height() + padding * 2;
+    let mut canvas = RgbaImage::from_fn(width, height, |x, y| {
+        if !settings.gradient {
+            return Rgba([settings.background[0], settings.background[1], settings.background[2], 255]);
+        }
+        let t = (x as f32 / width as f32 + y as f32 / height as f32) / 2.;
+        let mut pixel = [255; 4];
+        for i in 0..3 {
+            pixel[i] = (settings.background[i] as f32 * (1. - t) + settings.gradient_color[i] as f32 * t).round() as u8;
+        }
+        Rgba(pixel)
+    });
+
+    if settings.shadow_opacity > 0. {
+        // The shadow is the blurred shape of the content, with room around it for the blur to spread
+        let blur = settings.shadow_blur * scale;
+        let margin = (blur * 3.).ceil() as u32;
+        let mut mask = GrayImage::new(content.width() + margin * 2, content.height() + margin * 2);
+        for (x, y, pixel) in content.enumerate_pixels() {
+            mask.put_pixel(x + margin, y + margin, Luma([pixel[3]]));
+        }
+        if blur > 0. {
+            mask = gaussian_blur_f32(&mask, blur);
+        }
+
+        let offset_x = padding as i64 - margin as i64 + (settings.shadow_offset.0 as f32 * scale).round() as i64;
+        let offset_y = padding as i64 - margin as i64 + (settings.shadow_offset.1 as f32 * scale).round() as i64;
+        for (x, y, value) in mask.enumerate_pixels() {
+            let (canvas_x, canvas_y) = (x as i64 + offset_x, y as i64 + offset_y);
+            if canvas_x < 0 || canvas_y < 0 || canvas_x >= width as i64 || canvas_y >= height as i64 {
+                continue;
+            }
+            let alpha = value[0] as f32 / 255. * settings.shadow_opacity.min(1.);
+            let pixel = canvas.get_pixel_mut(canvas_x as u32, canvas_y as u32);
+            for i in 0..3 {
+                pixel[i] = (pixel[i] as f32 * (1. - alpha)).round() as u8;
+            }
+        }
+    }
+
+    imageops::overlay(&mut canvas, &content, padding as i64, padding as i64);
+    return DynamicImage::ImageRgba8(canvas);
+}
+
+fn add_title_bar(img: &RgbaImage, height: u32) -> RgbaImage {
+    let mut result = DynamicImage::ImageRgba8(RgbaImage::from_pixel(img.width(), img.height() + height, Rgba([232, 232, 232, 255])));
+    imageops::replace(&mut result, img, 0, height as i64);
+
+    // Close, minimize and maximize buttons
+    let radius = height as f32 * 0.22;
+    for (i, color) in [[255, 95, 87, 255], [254, 188, 46, 255], [40, 200, 64, 255]].iter().enumerate() {
+        let center = (height as f32 / 2. + i as f32 * radius * 3.2, height as f32 / 2.);
+        draw_antialiased_segment(&mut result, center, center, radius * 2., *color);
+    }
+
+    return result.to_rgba8();
+}
+
+// Makes the corners transparent outside of circles of the given radius
+fn round_corners(img: &mut RgbaImage, radius: f32) {
+    let radius = radius.min(img.width().min(img.height()) as f32 / 2.);
+    if radius <= 0. {
+        return;
+    }
+
+    let (width, height) = img.dimensions();
+    for y in 0..radius.ceil() as u32 {
+        for x in 0..radius.ceil() as u32 {
+            let dx = radius - (x as f32 + 0.5);
+            let dy = radius - (y as f32 + 0.5);
+            if dx <= 0. || dy <= 0. {
+                continue;
+            }
+            let coverage = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0., 1.);
+            for (corner_x, corner_y) in [(x, y), (width - 1 - x, y), (x, height - 1 - y), (width - 1 - x, height - 1 - y)] {
+                let pixel = img.get_pixel_mut(corner_x, corner_y);
+                pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_settings() -> BeautifySettings {
+        return BeautifySettings {
+            enabled: true,
+            padding: 20,
+            background: [10, 20, 30],
+            gradient: false,
+            gradient_color: [200, 100, 0],
+            corner_radius: 0,
+            shadow_blur: 0.,
+            shadow_offset: (0, 0),
+            shadow_opacity: 0.,
+            window_chrome: false,
+        };
+    }
+
+    #[test]
+    fn adds_the_padding_and_title_bar_to_the_size() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 50, Rgba([255, 255, 255, 255])));
+        let mut settings = get_settings();
+
+        let result = beautify_image(&img, &settings, 1.);
+        assert_eq!((result.width(), result.height()), (140, 90));
+
+        settings.window_chrome = true;
+        let result = beautify_image(&img, &settings, 1.);
+        assert_eq!((result.width(), result.height()), (140, 90 + TITLE_BAR_HEIGHT as u32));
+        // Previews scale every size
+        let result = beautify_image(&img, &settings, 0.5);
+        assert_eq!((result.width(), result.height()), (120, 70 + TITLE_BAR_HEIGHT as u32 / 2));
+    }
+
+    #[test]
+    fn fills_the_padding_with_the_background() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 50, Rgba([255, 255, 255, 255])));
+        let mut settings = get_settings();
+
+        let result = beautify_image(&img, &settings, 1.).to_rgba8();
+        assert_eq!(result.get_pixel(0, 0), &Rgba([10, 20, 30, 255]));
+        assert_eq!(result.get_pixel(139, 89), &Rgba([10, 20, 30, 255]));
+        assert_eq!(result.get_pixel(20, 20), &Rgba([255, 255, 255, 255]));
+
+        // The gradient goes from the background at the top left to the gradient color at the bottom right
+        settings.gradient = true;
+        let result = beautify_image(&img, &settings, 1.).to_rgba8();
+        assert_eq!(result.get_pixel(0, 0), &Rgba([10, 20, 30, 255]));
+        let corner = result.get_pixel(139, 89);
+        for i in 0..3 {
+            assert!((corner[i] as i32 - settings.gradient_color[i] as i32).abs() <= 3, "{:?}", corner);
+        }
+        assert_eq!(result.get_pixel(20, 20), &Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn makes_the_corners_transparent() {
+        let mut img = RgbaImage::from_pixel(40, 30, Rgba([255, 255, 255, 255]));
+        round_corners(&mut img, 10.);
+
+        for (x, y) in [(0, 0), (39, 0), (0, 29), (39, 29), (1, 1), (38, 28)] {
+            assert_eq!(img.get_pixel(x, y)[3], 0, "{} {}", x, y);
+        }
+        for (x, y) in [(10, 0), (0, 10), (20, 15), (29, 29), (39, 19)] {
+            assert_eq!(img.get_pixel(x, y)[3], 255, "{} {}", x, y);
+        }
+
+        // The rounded corners show the background in the padding
+        let mut settings = get_settings();
+        settings.corner_radius = 10;
+        let result = beautify_image(&DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 30, Rgba([255, 255, 255, 255]))), &settings, 1.).to_rgba8();
+        assert_eq!(result.get_pixel(20, 20), &Rgba([10, 20, 30, 255]));
+        assert_eq!(result.get_pixel(40, 35), &Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn puts_the_image_under_the_title_bar() {
+        let img = RgbaImage::from_pixel(100, 20, Rgba([0, 0, 255, 255]));
+        let result = add_title_bar(&img, 28);
+
+        assert_eq!(result.dimensions(), (100, 48));
+        assert_eq!(result.get_pixel(99, 0), &Rgba([232, 232, 232, 255]));
+        assert_eq!(result.get_pixel(99, 27), &Rgba([232, 232, 232, 255]));
+        assert_eq!(result.get_pixel(0, 28), &Rgba([0, 0, 255, 255]));
+        // The close button
+        assert_eq!(result.get_pixel(14, 14), &Rgba([255, 95, 87, 255]));
+    }
+}
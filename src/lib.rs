@@ -0,0 +1,10 @@
+//! Screen grabbing library behind SnapRust: capture sources, an editable
+//! image document and export helpers, independent of the graphical interface.
+
+pub mod tool_utils;
+pub mod hotkeys_utils;
+pub mod capture_utils;
+pub mod export_utils;
+pub mod adjust_utils;
+pub mod document;
+pub mod annotation;
@@ -1,8 +1,14 @@
-pub mod app;
+mod app;
+mod cli;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args));
+    }
+
     let mut native_options = eframe::NativeOptions::default();
-    native_options.min_window_size = Some(eframe::egui::Vec2::new(750., 500.));
+    native_options.min_window_size = Some(app::COUNTDOWN_WINDOW_SIZE);
 
     eframe::run_native(
         "SnapRust",
@@ -10,4 +16,4 @@ fn main() {
         Box::new(|cc| Box::new(app::SnapRustApp::new(cc))),
     )
     .unwrap();
-}
\ No newline at end of file
+}
@@ -0,0 +1,1402 @@
+use eframe::egui::{CursorIcon, Pos2, Vec2};
+use image::{imageops, DynamicImage, GenericImage, GenericImageView, GrayImage, Rgba, RgbaImage};
+use image::imageops::FilterType;
+use imageproc::drawing::{draw_filled_circle_mut, draw_filled_rect_mut, draw_filled_ellipse_mut};
+use imageproc::rect::Rect;
+use rusttype::{point, Font, GlyphId, PositionedGlyph, Scale};
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Tool {
+    None,
+    Pen,
+    Crop,
+    Rectangle,
+    Ellipse,
+    Arrow,
+    Text,
+    Highlighter,
+    Redact,
+    Eraser,
+    Step,
+    ColorPicker,
+    Select
+}
+
+// Text being typed on the snapshot, pos is the top left corner in image pixels
+pub struct TextBox {
+    pub pos: Pos2,
+    pub text: String,
+    pub focused: bool,
+}
+
+impl TextBox {
+    pub fn new(pos: Pos2) -> Self {
+        TextBox {
+            pos: pos,
+            text: String::new(),
+            focused: false
+        }
+    }
+}
+
+// Part of the selected annotation being dragged. Resize holds the side of the bounding box that
+// moves on each axis: -1 for the left or top one, 1 for the right or bottom one, 0 for neither.
+#[derive(PartialEq, Clone, Copy)]
+pub enum SelectionHandle {
+    Move,
+    Resize(i32, i32),
+    Rotate
+}
+
+pub fn get_handle_cursor(handle: SelectionHandle) -> CursorIcon {
+    match handle {
+        SelectionHandle::Move => CursorIcon::Move,
+        SelectionHandle::Rotate => CursorIcon::Grab,
+        SelectionHandle::Resize(0, _) => CursorIcon::ResizeVertical,
+        SelectionHandle::Resize(_, 0) => CursorIcon::ResizeHorizontal,
+        SelectionHandle::Resize(x, y) if x == y => CursorIcon::ResizeNwSe,
+        SelectionHandle::Resize(_, _) => CursorIcon::ResizeNeSw,
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum ArrowHeads {
+    Single,
+    Double
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum ArrowStyle {
+    Open,
+    Filled
+}
+
+pub fn get_line_points(x0: i32, y0: i32, x1: i32, y1: i32) -> Vec<(i32, i32)> {
+    let mut result = Vec::new();
+    
+    let dx = (x1 - x0).abs();
+    let dy = (y1 - y0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let sy = if y0 < y1 { 1 } else { -1 };
+    let (mut x, mut y) = (x0, y0);
+    let mut err = dx - dy;
+
+    loop {
+        result.push((x, y));
+
+        if x == x1 && y == y1 {
+            break;
+        }
+
+        let e2 = 2 * err;
+        if e2 > -dy {
+            err -= dy;
+            x += sx;
+        }
+        if e2 < dx {
+            err += dx;
+            y += sy;
+        }
+    }
+
+    result
+}
+
+
+
+pub fn draw_line(img: &mut DynamicImage, start:(f32, f32), end:(f32, f32), t: i32, color: [u8; 4]) {
+    let segment = get_line_points(start.0 as i32, start.1 as i32, end.0 as i32, end.1 as i32);
+    for point in segment {
+        draw_filled_circle_mut(img, (point.0, point.1), t, color.into());
+    }
+}
+
+
+
+pub fn draw_polyline(img: &mut DynamicImage, points: &[(f32, f32)], t: i32, color: [u8; 4]) {
+    for segment in points.windows(2) {
+        draw_line(img, segment[0], segment[1], t, color);
+    }
+}
+
+// Rotates point around center by angle radians, clockwise on screen
+pub fn rotate_point(point: (f32, f32), center: (f32, f32), angle: f32) -> (f32, f32) {
+    let (sin, cos) = angle.sin_cos();
+    let offset = (point.0 - center.0, point.1 - center.1);
+    return (center.0 + offset.0 * cos - offset.1 * sin, center.1 + offset.0 * sin + offset.1 * cos);
+}
+
+// Corners of the rectangle between start and end turned by angle around its center, first and last point match
+pub fn get_rectangle_points(start:(f32, f32), end:(f32, f32), angle: f32) -> Vec<(f32, f32)> {
+    let center = ((start.0 + end.0) / 2., (start.1 + end.1) / 2.);
+    return [start, (end.0, start.1), end, (start.0, end.1), start].iter()
+        .map(|corner| rotate_point(*corner, center, angle))
+        .collect();
+}
+
+pub fn draw_rectangle(img: &mut DynamicImage, start:(f32, f32), end:(f32, f32), t: i32, color: [u8; 4], filled: bool, angle: f32) {
+    if angle != 0. {
+        let points = get_rectangle_points(start, end, angle);
+        if filled {
+            draw_antialiased_polygon(img, &points[..4], color);
+        }
+        else {
+            draw_polyline(img, &points, t, color);
+        }
+        return;
+    }
+
+    let min = (start.0.min(end.0), start.1.min(end.1));
+    let max = (start.0.max(end.0), start.1.max(end.1));
+
+    if filled {
+        let width = (max.0 - min.0).round() as u32;
+        let height = (max.1 - min.1).round() as u32;
+        if width > 0 && height > 0 {
+            draw_filled_rect_mut(img, Rect::at(min.0 as i32, min.1 as i32).of_size(width, height), color.into());
+        }
+    }
+    else {
+        draw_polyline(img, &[min, (max.0, min.1), max, (min.0, max.1), min], t, color);
+    }
+}
+
+// Points along the ellipse inscribed in the rectangle between start and end, turned by angle around
+// its center. First and last point match.
+pub fn get_ellipse_points(start:(f32, f32), end:(f32, f32), angle: f32) -> Vec<(f32, f32)> {
+    let center = ((start.0 + end.0) / 2., (start.1 + end.1) / 2.);
+    let radius = ((end.0 - start.0).abs() / 2., (end.1 - start.1).abs() / 2.);
+
+    // Roughly one point every few pixels of perimeter
+    let steps = ((radius.0 + radius.1) * std::f32::consts::PI / 3.).max(16.) as usize;
+
+    let mut result = Vec::new();
+    for i in 0..=steps {
+        let step_angle = i as f32 / steps as f32 * std::f32::consts::TAU;
+        result.push(rotate_point((center.0 + radius.0 * step_angle.cos(), center.1 + radius.1 * step_angle.sin()), center, angle));
+    }
+
+    result
+}
+
+pub fn draw_ellipse(img: &mut DynamicImage, start:(f32, f32), end:(f32, f32), t: i32, color: [u8; 4], filled: bool, angle: f32) {
+    if filled && angle != 0. {
+        draw_antialiased_polygon(img, &get_ellipse_points(start, end, angle), color);
+    }
+    else if filled {
+        let center = (((start.0 + end.0) / 2.) as i32, ((start.1 + end.1) / 2.) as i32);
+        let width_radius = ((end.0 - start.0).abs() / 2.) as i32;
+        let height_radius = ((end.1 - start.1).abs() / 2.) as i32;
+        draw_filled_ellipse_mut(img, center, width_radius, height_radius, color.into());
+    }
+    else {
+        draw_polyline(img, &get_ellipse_points(start, end, angle), t, color);
+    }
+}
+
+
+
+// Alpha blends color over the pixel, coverage in [0, 1] scales the color alpha
+pub fn blend_pixel(img: &mut DynamicImage, x: i32, y: i32, color: [u8; 4], coverage: f32) {
+    if x < 0 || y < 0 || x >= img.width() as i32 || y >= img.height() as i32 || coverage <= 0. {
+        return;
+    }
+
+    let alpha = color[3] as f32 / 255. * coverage.min(1.);
+    let pixel = img.get_pixel(x as u32, y as u32).0;
+    let mut blended = [0; 4];
+    for i in 0..3 {
+        blended[i] = (color[i] as f32 * alpha + pixel[i] as f32 * (1. - alpha)).round() as u8;
+    }
+    blended[3] = (255. * alpha + pixel[3] as f32 * (1. - alpha)).round() as u8;
+
+    img.put_pixel(x as u32, y as u32, Rgba(blended));
+}
+
+pub fn distance_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let ab = (b.0 - a.0, b.1 - a.1);
+    let ap = (p.0 - a.0, p.1 - a.1);
+    let length_sq = ab.0 * ab.0 + ab.1 * ab.1;
+
+    let t = if length_sq > 0. { ((ap.0 * ab.0 + ap.1 * ab.1) / length_sq).clamp(0., 1.) } else { 0. };
+    let closest = (a.0 + ab.0 * t, a.1 + ab.1 * t);
+
+    return ((p.0 - closest.0).powi(2) + (p.1 - closest.1).powi(2)).sqrt();
+}
+
+// Calls f with every pixel of a width x height image covered by a thick segment with round caps,
+// and its coverage. The covered part of a row is a single span, so each row is walked outwards
+// from the segment until the coverage drops to zero instead of testing the whole bounding box.
+fn for_each_segment_pixel<F: FnMut(u32, u32, f32)>(width: u32, height: u32, start:(f32, f32), end:(f32, f32), radius: f32, mut f: F) {
+    let ab = (end.0 - start.0, end.1 - start.1);
+    let length_sq = ab.0 * ab.0 + ab.1 * ab.1;
+    let inv_length_sq = if length_sq > 0. { 1. / length_sq } else { 0. };
+    let get_coverage = |x: f32, y: f32| {
+        let ap = (x - start.0, y - start.1);
+        let t = ((ap.0 * ab.0 + ap.1 * ab.1) * inv_length_sq).clamp(0., 1.);
+        let distance = ((ap.0 - ab.0 * t).powi(2) + (ap.1 - ab.1 * t).powi(2)).sqrt();
+        (radius + 0.5 - distance).clamp(0., 1.)
+    };
+
+    let min_y = ((start.1.min(end.1) - radius - 1.).floor() as i64).max(0);
+    let max_y = ((start.1.max(end.1) + radius + 1.).ceil() as i64).min(height as i64 - 1);
+
+    for y in min_y..=max_y {
+        let center_y = y as f32 + 0.5;
+
+        // A point of the segment inside the covered span of this row
+        let span_x = if start.1 == end.1 {
+            (start.0 + end.0) / 2.
+        } else if center_y <= start.1.min(end.1) {
+            if start.1 < end.1 { start.0 } else { end.0 }
+        } else if center_y >= start.1.max(end.1) {
+            if start.1 > end.1 { start.0 } else { end.0 }
+        } else {
+            start.0 + (center_y - start.1) / (end.1 - start.1) * (end.0 - start.0)
+        };
+
+        let first_x = ((span_x - 0.5).ceil() as i64).clamp(0, width as i64);
+        for x in first_x..width as i64 {
+            let coverage = get_coverage(x as f32 + 0.5, center_y);
+            if coverage <= 0. {
+                break;
+            }
+            f(x as u32, y as u32, coverage);
+        }
+        for x in (0..first_x).rev() {
+            let coverage = get_coverage(x as f32 + 0.5, center_y);
+            if coverage <= 0. {
+                break;
+            }
+            f(x as u32, y as u32, coverage);
+        }
+    }
+}
+
+// Turns a pixel blended with the color at the old coverage into one blended at the new coverage,
+// without having to keep the pixel as it was before the first blend
+fn reblend_pixel(pixel: &mut Rgba<u8>, color: [u8; 4], old_coverage: f32, new_coverage: f32) {
+    let old_alpha = color[3] as f32 / 255. * old_coverage;
+    let new_alpha = color[3] as f32 / 255. * new_coverage;
+    if old_alpha >= 1. {
+        return;
+    }
+    let ratio = (1. - new_alpha) / (1. - old_alpha);
+
+    for i in 0..3 {
+        pixel.0[i] = (color[i] as f32 + (pixel.0[i] as f32 - color[i] as f32) * ratio).round() as u8;
+    }
+    pixel.0[3] = (255. + (pixel.0[3] as f32 - 255.) * ratio).round() as u8;
+}
+
+// Smooths out the jitter of the drag points. With smoothing 0 the points are kept as they are, values
+// closer to 1 trail further behind the pointer. The last point is kept so the stroke ends where the drag did.
+pub fn smooth_points(points: &[(f32, f32)], smoothing: f32) -> Vec<(f32, f32)> {
+    let factor = 1. - smoothing.clamp(0., 0.95);
+    let mut result: Vec<(f32, f32)> = Vec::with_capacity(points.len() + 1);
+
+    for point in points {
+        let smoothed = match result.last() {
+            Some(last) => (last.0 + (point.0 - last.0) * factor, last.1 + (point.1 - last.1) * factor),
+            None => *point,
+        };
+        result.push(smoothed);
+    }
+    if let Some(last) = points.last() {
+        if points.len() > 1 && factor < 1. {
+            result.push(*last);
+        }
+    }
+
+    return result;
+}
+
+// Anti-aliased stroke through the drag points with round caps and joins. The coverage of every
+// segment is merged into a mask and each pixel holds the color blended once at its highest
+// coverage, so joins are not blended twice and translucent colors do not get darker where the
+// stroke overlaps itself.
+pub struct BrushStroke {
+    mask: GrayImage,
+    color: [u8; 4],
+    width: f32,
+    last_point: (f32, f32),
+}
+
+impl BrushStroke {
+    // Converts the image to RGBA if needed, the stroke writes the pixels directly
+    pub fn new(img: &mut DynamicImage, start: (f32, f32), color: [u8; 4], width: f32) -> Self {
+        if img.as_rgba8().is_none() {
+            *img = DynamicImage::ImageRgba8(img.to_rgba8());
+        }
+
+        let mut stroke = BrushStroke {
+            mask: GrayImage::new(img.width(), img.height()),
+            color: color,
+            width: width,
+            last_point: start
+        };
+        stroke.add_segment(img, start, start);
+
+        return stroke;
+    }
+
+    pub fn add_point(&mut self, img: &mut DynamicImage, point: (f32, f32)) {
+        self.add_segment(img, self.last_point, point);
+        self.last_point = point;
+    }
+
+    fn add_segment(&mut self, img: &mut DynamicImage, start:(f32, f32), end:(f32, f32)) {
+        let (mask, color) = (&mut self.mask, self.color);
+        let img = img.as_mut_rgba8().unwrap();
+        for_each_segment_pixel(img.width(), img.height(), start, end, self.width / 2., |x, y, coverage| {
+            let coverage_u8 = (coverage * 255.).round() as u8;
+
+            let mask_pixel = mask.get_pixel_mut(x, y);
+            if coverage_u8 > mask_pixel.0[0] {
+                reblend_pixel(img.get_pixel_mut(x, y), color, mask_pixel.0[0] as f32 / 255., coverage_u8 as f32 / 255.);
+                mask_pixel.0[0] = coverage_u8;
+            }
+        });
+    }
+}
+
+// The stroke alone on a transparent image that covers it, and the image pixel its top left corner
+// goes on. Shown over the capture it gives the same pixels as drawing the stroke into the capture.
+pub fn get_stroke_image(points: &[(f32, f32)], color: [u8; 4], width: f32) -> Option<((i32, i32), RgbaImage)> {
+    let start = *points.first()?;
+    let margin = width / 2. + 1.;
+    let min_x = (points.iter().map(|p| p.0).fold(f32::MAX, f32::min) - margin).floor() as i32;
+    let min_y = (points.iter().map(|p| p.1).fold(f32::MAX, f32::min) - margin).floor() as i32;
+    let max_x = (points.iter().map(|p| p.0).fold(f32::MIN, f32::max) + margin).ceil() as i32;
+    let max_y = (points.iter().map(|p| p.1).fold(f32::MIN, f32::max) + margin).ceil() as i32;
+    let to_local = |point: (f32, f32)| (point.0 - min_x as f32, point.1 - min_y as f32);
+
+    // An opaque white stroke on transparent pixels leaves its coverage in the alpha channel
+    let mut img = DynamicImage::ImageRgba8(RgbaImage::new((max_x - min_x) as u32, (max_y - min_y) as u32));
+    let mut stroke = BrushStroke::new(&mut img, to_local(start), [255, 255, 255, 255], width);
+    for point in &points[1..] {
+        stroke.add_point(&mut img, to_local(*point));
+    }
+
+    let mut img = img.into_rgba8();
+    for pixel in img.pixels_mut() {
+        let alpha = (pixel.0[3] as f32 * color[3] as f32 / 255.).round() as u8;
+        *pixel = Rgba([color[0], color[1], color[2], alpha]);
+    }
+
+    return Some(((min_x, min_y), img));
+}
+
+// Copies the pixels of source under a thick segment with round caps, both images must have the same size
+pub fn restore_segment(img: &mut DynamicImage, source: &DynamicImage, start:(f32, f32), end:(f32, f32), width: f32) {
+    for_each_segment_pixel(img.width(), img.height(), start, end, width / 2., |x, y, coverage| {
+        let current = img.get_pixel(x, y).0;
+        let restored = source.get_pixel(x, y).0;
+        let mut mixed = [0u8; 4];
+        for i in 0..4 {
+            mixed[i] = (current[i] as f32 + (restored[i] as f32 - current[i] as f32) * coverage).round() as u8;
+        }
+        img.put_pixel(x, y, Rgba(mixed));
+    });
+}
+
+// Thick segment with round caps, edge pixels get partial coverage
+pub fn draw_antialiased_segment(img: &mut DynamicImage, start:(f32, f32), end:(f32, f32), width: f32, color: [u8; 4]) {
+    let (img_width, img_height) = (img.width(), img.height());
+    for_each_segment_pixel(img_width, img_height, start, end, width / 2., |x, y, coverage| {
+        blend_pixel(img, x as i32, y as i32, color, coverage);
+    });
+}
+
+fn is_inside_polygon(p: (f32, f32), points: &[(f32, f32)]) -> bool {
+    let mut inside = false;
+    let mut j = points.len() - 1;
+    for i in 0..points.len() {
+        let (a, b) = (points[i], points[j]);
+        if (a.1 > p.1) != (b.1 > p.1) && p.0 < (b.0 - a.0) * (p.1 - a.1) / (b.1 - a.1) + a.0 {
+            inside = !inside;
+        }
+        j = i;
+    }
+    return inside;
+}
+
+pub fn draw_antialiased_polygon(img: &mut DynamicImage, points: &[(f32, f32)], color: [u8; 4]) {
+    if points.len() < 3 {
+        return;
+    }
+
+    let min_x = points.iter().map(|p| p.0).fold(f32::MAX, f32::min).floor() as i32 - 1;
+    let min_y = points.iter().map(|p| p.1).fold(f32::MAX, f32::min).floor() as i32 - 1;
+    let max_x = points.iter().map(|p| p.0).fold(f32::MIN, f32::max).ceil() as i32 + 1;
+    let max_y = points.iter().map(|p| p.1).fold(f32::MIN, f32::max).ceil() as i32 + 1;
+
+    for y in min_y.max(0)..=max_y.min(img.height() as i32 - 1) {
+        for x in min_x.max(0)..=max_x.min(img.width() as i32 - 1) {
+            let p = (x as f32 + 0.5, y as f32 + 0.5);
+            let mut distance = f32::MAX;
+            for i in 0..points.len() {
+                distance = distance.min(distance_to_segment(p, points[i], points[(i + 1) % points.len()]));
+            }
+
+            let coverage = if is_inside_polygon(p, points) { 0.5 + distance } else { 0.5 - distance };
+            blend_pixel(img, x, y, color, coverage.clamp(0., 1.));
+        }
+    }
+}
+
+// Snaps end so that the segment from start is a multiple of step degrees
+pub fn snap_angle(start: Pos2, end: Pos2, step: f32) -> Pos2 {
+    let delta = end - start;
+    let step = step.to_radians();
+    let angle = (delta.y.atan2(delta.x) / step).round() * step;
+    return start + Vec2::angled(angle) * delta.length();
+}
+
+pub struct ArrowGeometry {
+    pub shaft: ((f32, f32), (f32, f32)),
+    // Each head is [left corner, tip, right corner]
+    pub heads: Vec<[(f32, f32); 3]>,
+}
+
+// Shaft and heads of an arrow of the given line width, the head grows with the width
+pub fn get_arrow_geometry(start:(f32, f32), end:(f32, f32), width: f32, heads: ArrowHeads, style: ArrowStyle) -> ArrowGeometry {
+    let length = ((end.0 - start.0).powi(2) + (end.1 - start.1).powi(2)).sqrt();
+    if length == 0. {
+        return ArrowGeometry { shaft: (start, end), heads: Vec::new() };
+    }
+
+    let direction = ((end.0 - start.0) / length, (end.1 - start.1) / length);
+    let normal = (-direction.1, direction.0);
+    let head_length = (width * 3. + 6.).min(length * if heads == ArrowHeads::Double { 0.5 } else { 1. });
+    let head_width = head_length * 0.6;
+
+    let get_head = |tip: (f32, f32), direction: (f32, f32)| -> [(f32, f32); 3] {
+        let base = (tip.0 - direction.0 * head_length, tip.1 - direction.1 * head_length);
+        [
+            (base.0 + normal.0 * head_width, base.1 + normal.1 * head_width),
+            tip,
+            (base.0 - normal.0 * head_width, base.1 - normal.1 * head_width)
+        ]
+    };
+
+    let mut arrow_heads = vec![get_head(end, direction)];
+    if heads == ArrowHeads::Double {
+        arrow_heads.push(get_head(start, (-direction.0, -direction.1)));
+    }
+
+    // A filled head covers the end of the shaft, stop it at the head base so it does not poke through the tip
+    let mut shaft = (start, end);
+    if style == ArrowStyle::Filled {
+        shaft.1 = (end.0 - direction.0 * head_length * 0.8, end.1 - direction.1 * head_length * 0.8);
+        if heads == ArrowHeads::Double {
+            shaft.0 = (start.0 + direction.0 * head_length * 0.8, start.1 + direction.1 * head_length * 0.8);
+        }
+    }
+
+    return ArrowGeometry { shaft: shaft, heads: arrow_heads };
+}
+
+pub fn draw_arrow(img: &mut DynamicImage, start:(f32, f32), end:(f32, f32), t: i32, color: [u8; 4], heads: ArrowHeads, style: ArrowStyle) {
+    let width = (t * 2 + 1) as f32;
+    let geometry = get_arrow_geometry(start, end, width, heads, style);
+
+    draw_antialiased_segment(img, geometry.shaft.0, geometry.shaft.1, width, color);
+    for head in geometry.heads.iter() {
+        match style {
+            ArrowStyle::Filled => draw_antialiased_polygon(img, head, color),
+            ArrowStyle::Open => {
+                draw_antialiased_segment(img, head[0], head[1], width, color);
+                draw_antialiased_segment(img, head[1], head[2], width, color);
+            }
+        }
+    }
+}
+
+
+
+// The fonts egui uses for its proportional text, so the typing preview matches the result.
+// The first font is the main one, the others are fallbacks for the characters it misses.
+pub fn get_default_fonts() -> Vec<Font<'static>> {
+    let font_definitions = eframe::egui::FontDefinitions::default();
+    let mut fonts = Vec::new();
+
+    for name in ["Ubuntu-Light", "NotoEmoji-Regular", "emoji-icon-font"] {
+        if let Some(font_data) = font_definitions.font_data.get(name) {
+            if let Some(font) = Font::try_from_vec(font_data.font.to_vec()) {
+                fonts.push(font);
+            }
+        }
+    }
+
+    return fonts;
+}
+
+// Accepts TrueType and OpenType font files, the file content is returned too so that the
+// interface can show the same font
+pub fn load_font(path: &std::path::Path) -> Option<(Font<'static>, Vec<u8>)> {
+    let bytes = std::fs::read(path).ok()?;
+    let font = Font::try_from_vec(bytes.clone())?;
+    return Some((font, bytes));
+}
+
+// Lays out a single line of text picking, for each character, the first font that has it.
+// Returns the width of the line.
+fn layout_text<'f, F: FnMut(PositionedGlyph<'f>)>(text: &str, fonts: &[Font<'f>], size: f32, origin: (f32, f32), mut f: F) -> f32 {
+    let scale = Scale::uniform(size);
+    let ascent = fonts[0].v_metrics(scale).ascent;
+    let mut x = origin.0;
+    let mut last: Option<(usize, GlyphId)> = None;
+
+    for c in text.chars() {
+        let font_index = fonts.iter().position(|font| font.glyph(c).id().0 != 0).unwrap_or(0);
+        let font = &fonts[font_index];
+        let glyph = font.glyph(c).scaled(scale);
+
+        if let Some((last_font_index, last_id)) = last {
+            if last_font_index == font_index {
+                x += font.pair_kerning(scale, last_id, glyph.id());
+            }
+        }
+        last = Some((font_index, glyph.id()));
+
+        let advance_width = glyph.h_metrics().advance_width;
+        f(glyph.positioned(point(x, origin.1 + ascent)));
+        x += advance_width;
+    }
+
+    return x - origin.0;
+}
+
+// Size in pixels of the text box drawn by draw_text, including the background padding
+pub fn get_text_size(text: &str, fonts: &[Font], size: f32) -> (f32, f32) {
+    let width = layout_text(text, fonts, size, (0., 0.), |_| {});
+    let padding = size * 0.2;
+    return (width + padding * 2., size + padding * 2.);
+}
+
+pub fn draw_text(img: &mut DynamicImage, pos: (f32, f32), text: &str, fonts: &[Font], size: f32, color: [u8; 4], background: Option<[u8; 4]>) {
+    let padding = size * 0.2;
+
+    if let Some(background) = background {
+        let (width, height) = get_text_size(text, fonts, size);
+        draw_filled_rect_mut(img, Rect::at(pos.0 as i32, pos.1 as i32).of_size(width.ceil() as u32, height.ceil() as u32), background.into());
+    }
+
+    layout_text(text, fonts, size, (pos.0 + padding, pos.1 + padding), |glyph| {
+        if let Some(bounding_box) = glyph.pixel_bounding_box() {
+            glyph.draw(|x, y, coverage| {
+                blend_pixel(img, bounding_box.min.x + x as i32, bounding_box.min.y + y as i32, color, coverage);
+            });
+        }
+    });
+}
+
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum RedactStyle {
+    Blur,
+    Pixelate,
+    Fill,
+}
+
+// Smallest blur radius and pixelate block that still hide text of a normal size, smaller blocks
+// can be read back by depixelation tools
+pub const MIN_REDACT_BLUR: u32 = 8;
+pub const MIN_REDACT_BLOCK: u32 = 8;
+
+// Overwrites the area in place. The blur radius or block size is given by strength, the fill
+// style uses color. Every pixel of the area is replaced, nothing of the original is kept.
+pub fn redact_image(img: &mut DynamicImage, x: u32, y: u32, width: u32, height: u32, style: RedactStyle, strength: u32, color: [u8; 4]) {
+    let width = width.min(img.width().saturating_sub(x));
+    let height = height.min(img.height().saturating_sub(y));
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    // Larger areas usually hold larger text, so the blur radius and block size grow with the area up to 64
+    let area_strength = (width.min(height) / 4).min(64);
+
+    match style {
+        RedactStyle::Blur => {
+            // Blur only the area so the edges do not pick up pixels from outside of it
+            let area = img.crop_imm(x, y, width, height).to_rgba8();
+            let sigma = strength.max(MIN_REDACT_BLUR).max(area_strength);
+            let mut blurred = imageops::blur(&area, sigma as f32);
+
+            // Quantizing removes the low bits that a deconvolution could use to recover the text
+            for pixel in blurred.pixels_mut() {
+                for channel in pixel.0.iter_mut().take(3) {
+                    *channel = *channel & 0xF0 | 0x08;
+                }
+            }
+            img.copy_from(&blurred, x, y).unwrap();
+        }
+        RedactStyle::Pixelate => {
+            let block_size = strength.max(MIN_REDACT_BLOCK).max(area_strength);
+            // The blocks split the area evenly, a thin block at the edge would keep the pixels readable
+            let columns = (width / block_size).max(1);
+            let rows = (height / block_size).max(1);
+            for row in 0..rows {
+                let block_y = y + row * height / rows;
+                let block_height = y + (row + 1) * height / rows - block_y;
+                for column in 0..columns {
+                    let block_x = x + column * width / columns;
+                    let block_width = x + (column + 1) * width / columns - block_x;
+
+                    let mut sum = [0u64; 4];
+                    for (_, _, pixel) in img.view(block_x, block_y, block_width, block_height).pixels() {
+                        for i in 0..4 {
+                            sum[i] += pixel.0[i] as u64;
+                        }
+                    }
+                    let count = (block_width * block_height) as u64;
+                    let average = Rgba(sum.map(|channel| (channel / count) as u8));
+
+                    draw_filled_rect_mut(img, Rect::at(block_x as i32, block_y as i32).of_size(block_width, block_height), average);
+                }
+            }
+        }
+        RedactStyle::Fill => {
+            draw_filled_rect_mut(img, Rect::at(x as i32, y as i32).of_size(width, height), Rgba(color));
+        }
+    }
+}
+
+// Font size and color of the number of a step marker, the text is black or white depending on the circle color
+pub fn get_step_marker_style(number: u32, radius: f32, color: [u8; 4]) -> (f32, [u8; 4]) {
+    let luminance = 0.299 * color[0] as f32 + 0.587 * color[1] as f32 + 0.114 * color[2] as f32;
+    let text_color = if luminance > 150. { [0, 0, 0, 255] } else { [255, 255, 255, 255] };
+
+    let size = match number.to_string().len() {
+        1 => radius * 1.3,
+        2 => radius * 1.1,
+        _ => radius * 0.8,
+    };
+
+    return (size, text_color);
+}
+
+// Filled circle with the number centered on it
+pub fn draw_step_marker(img: &mut DynamicImage, center: (f32, f32), radius: f32, number: u32, fonts: &[Font], color: [u8; 4]) {
+    let min_x = ((center.0 - radius - 1.).floor() as i32).max(0);
+    let min_y = ((center.1 - radius - 1.).floor() as i32).max(0);
+    let max_x = ((center.0 + radius + 1.).ceil() as i32).min(img.width() as i32 - 1);
+    let max_y = ((center.1 + radius + 1.).ceil() as i32).min(img.height() as i32 - 1);
+
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            let distance = ((x as f32 + 0.5 - center.0).powi(2) + (y as f32 + 0.5 - center.1).powi(2)).sqrt();
+            blend_pixel(img, x, y, color, (radius + 0.5 - distance).clamp(0., 1.));
+        }
+    }
+
+    let (size, text_color) = get_step_marker_style(number, radius, color);
+    let text = number.to_string();
+
+    // Center the ink of the digits rather than the line box, digits have no descent
+    let mut glyphs = Vec::new();
+    layout_text(&text, fonts, size, (0., 0.), |glyph| glyphs.push(glyph));
+    let boxes: Vec<_> = glyphs.iter().filter_map(|glyph| glyph.pixel_bounding_box()).collect();
+    if boxes.is_empty() {
+        return;
+    }
+    let ink_min_x = boxes.iter().map(|bounding_box| bounding_box.min.x).min().unwrap() as f32;
+    let ink_min_y = boxes.iter().map(|bounding_box| bounding_box.min.y).min().unwrap() as f32;
+    let ink_max_x = boxes.iter().map(|bounding_box| bounding_box.max.x).max().unwrap() as f32;
+    let ink_max_y = boxes.iter().map(|bounding_box| bounding_box.max.y).max().unwrap() as f32;
+    let offset_x = (center.0 - (ink_min_x + ink_max_x) / 2.).round() as i32;
+    let offset_y = (center.1 - (ink_min_y + ink_max_y) / 2.).round() as i32;
+
+    for glyph in glyphs {
+        if let Some(bounding_box) = glyph.pixel_bounding_box() {
+            glyph.draw(|x, y, coverage| {
+                blend_pixel(img, offset_x + bounding_box.min.x + x as i32, offset_y + bounding_box.min.y + y as i32, text_color, coverage);
+            });
+        }
+    }
+}
+
+// Largest width or height the resize dialog allows
+pub const MAX_IMAGE_SIZE: u32 = 16384;
+
+// Settings of the resize dialog, original is the size of the capture when it was opened
+pub struct ResizeDialog {
+    pub original: (u32, u32),
+    pub width: u32,
+    pub height: u32,
+    pub lock_aspect: bool,
+    pub filter: FilterType,
+}
+
+impl ResizeDialog {
+    pub fn new(width: u32, height: u32) -> Self {
+        ResizeDialog {
+            original: (width, height),
+            width: width,
+            height: height,
+            lock_aspect: true,
+            filter: FilterType::Lanczos3
+        }
+    }
+
+    // Size compared to the original, in percent
+    pub fn get_percentage(&self) -> f32 {
+        return self.width as f32 / self.original.0 as f32 * 100.;
+    }
+
+    // The percentage is lowered if needed so that neither side goes over MAX_IMAGE_SIZE
+    pub fn set_percentage(&mut self, percentage: f32) {
+        let percentage = percentage.min(MAX_IMAGE_SIZE as f32 * 100. / self.original.0.max(self.original.1) as f32);
+        self.width = ((self.original.0 as f32 * percentage / 100.).round() as u32).clamp(1, MAX_IMAGE_SIZE);
+        self.height = ((self.original.1 as f32 * percentage / 100.).round() as u32).clamp(1, MAX_IMAGE_SIZE);
+    }
+
+    // Keeps the original aspect ratio after the width changed, if it is locked
+    pub fn update_height(&mut self) {
+        if self.lock_aspect {
+            self.height = ((self.width as f32 * self.original.1 as f32 / self.original.0 as f32).round() as u32).clamp(1, MAX_IMAGE_SIZE);
+        }
+    }
+
+    // Keeps the original aspect ratio after the height changed, if it is locked
+    pub fn update_width(&mut self) {
+        if self.lock_aspect {
+            self.width = ((self.height as f32 * self.original.0 as f32 / self.original.1 as f32).round() as u32).clamp(1, MAX_IMAGE_SIZE);
+        }
+    }
+}
+
+pub fn get_filter_label(filter: FilterType) -> &'static str {
+    match filter {
+        FilterType::Nearest => "Nearest",
+        FilterType::Triangle => "Bilinear",
+        FilterType::CatmullRom => "Catmull-Rom",
+        FilterType::Gaussian => "Gaussian",
+        FilterType::Lanczos3 => "Lanczos3",
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum AspectRatio {
+    Free,
+    Square,
+    Wide,
+    Standard
+}
+
+impl AspectRatio {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AspectRatio::Free => "Free",
+            AspectRatio::Square => "1:1",
+            AspectRatio::Wide => "16:9",
+            AspectRatio::Standard => "4:3",
+        }
+    }
+
+    // Width divided by height
+    pub fn value(&self) -> Option<f32> {
+        match self {
+            AspectRatio::Free => None,
+            AspectRatio::Square => Some(1.),
+            AspectRatio::Wide => Some(16. / 9.),
+            AspectRatio::Standard => Some(4. / 3.),
+        }
+    }
+}
+
+// The crop rectangle after dragging its sides given by handle (see SelectionHandle::Resize) to pos,
+// kept inside an image of image_size pixels and to the aspect ratio if there is one
+pub fn resize_crop_rect(rect: egui::Rect, handle: (i32, i32), pos: Pos2, aspect: Option<f32>, image_size: Vec2) -> egui::Rect {
+    let pos = pos.clamp(Pos2::ZERO, image_size.to_pos2());
+    // The sides that stay in place
+    let anchor = Pos2::new(
+        if handle.0 < 0 { rect.max.x } else { rect.min.x },
+        if handle.1 < 0 { rect.max.y } else { rect.min.y }
+    );
+
+    let mut min = rect.min;
+    let mut max = rect.max;
+    if handle.0 != 0 {
+        min.x = anchor.x.min(pos.x);
+        max.x = anchor.x.max(pos.x);
+    }
+    if handle.1 != 0 {
+        min.y = anchor.y.min(pos.y);
+        max.y = anchor.y.max(pos.y);
+    }
+
+    let aspect = match aspect {
+        Some(aspect) if max.x > min.x || max.y > min.y => aspect,
+        _ => return egui::Rect::from_min_max(min, max),
+    };
+
+    // Follow the dragged side, or the one that moved the most for a corner, and grow or shrink the
+    // other one. A side handle keeps the rectangle centered on the other axis.
+    let mut size = max - min;
+    match handle {
+        (0, _) => size.x = size.y * aspect,
+        (_, 0) => size.y = size.x / aspect,
+        _ if size.x < size.y * aspect => size.x = size.y * aspect,
+        _ => size.y = size.x / aspect,
+    }
+
+    let center = rect.center();
+    let direction = Vec2::new(if pos.x < anchor.x { -1. } else { 1. }, if pos.y < anchor.y { -1. } else { 1. });
+    let available = Vec2::new(
+        if handle.0 == 0 { 2. * center.x.min(image_size.x - center.x) } else if direction.x < 0. { anchor.x } else { image_size.x - anchor.x },
+        if handle.1 == 0 { 2. * center.y.min(image_size.y - center.y) } else if direction.y < 0. { anchor.y } else { image_size.y - anchor.y }
+    );
+    size *= (available.x / size.x).min(available.y / size.y).min(1.);
+
+    let min = Pos2::new(
+        if handle.0 == 0 { center.x - size.x / 2. } else if direction.x < 0. { anchor.x - size.x } else { anchor.x },
+        if handle.1 == 0 { center.y - size.y / 2. } else if direction.y < 0. { anchor.y - size.y } else { anchor.y }
+    );
+    return egui::Rect::from_min_size(min, size);
+}
+
+// Makes the crop rectangle follow the aspect ratio by changing its height, then shrinks and moves
+// it as little as needed to fit inside an image of image_size pixels
+pub fn fit_crop_rect(rect: egui::Rect, aspect: Option<f32>, image_size: Vec2) -> egui::Rect {
+    let mut size = rect.size().max(Vec2::splat(1.));
+    if let Some(aspect) = aspect {
+        size.y = size.x / aspect;
+    }
+    // The scaled size can come out a rounding error larger than the image
+    size = (size * (image_size.x / size.x).min(image_size.y / size.y).min(1.)).min(image_size);
+
+    let min = rect.min.clamp(Pos2::ZERO, (image_size - size).max(Vec2::ZERO).to_pos2());
+    return egui::Rect::from_min_size(min, size);
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Transform {
+    RotateLeft,
+    RotateRight,
+    Rotate180,
+    FlipHorizontal,
+    FlipVertical
+}
+
+impl Transform {
+    pub fn apply_to_image(&self, img: &DynamicImage) -> DynamicImage {
+        match self {
+            Transform::RotateLeft => img.rotate270(),
+            Transform::RotateRight => img.rotate90(),
+            Transform::Rotate180 => img.rotate180(),
+            Transform::FlipHorizontal => img.fliph(),
+            Transform::FlipVertical => img.flipv(),
+        }
+    }
+
+    // Where a point of an image of the given size ends up
+    pub fn apply_to_point(&self, point: (f32, f32), size: (f32, f32)) -> (f32, f32) {
+        match self {
+            Transform::RotateLeft => (point.1, size.0 - point.0),
+            Transform::RotateRight => (size.1 - point.1, point.0),
+            Transform::Rotate180 => (size.0 - point.0, size.1 - point.1),
+            Transform::FlipHorizontal => (size.0 - point.0, point.1),
+            Transform::FlipVertical => (point.0, size.1 - point.1),
+        }
+    }
+
+    // A flip mirrors the angles of the shapes turned by the select tool
+    pub fn is_flip(&self) -> bool {
+        return *self == Transform::FlipHorizontal || *self == Transform::FlipVertical;
+    }
+}
+
+// Crops the image to the given rectangle clamped to the image bounds, None if nothing is left
+pub fn crop_image(img: &DynamicImage, x: i64, y: i64, width: u32, height: u32) -> Option<DynamicImage> {
+    let min_x = x.clamp(0, img.width() as i64) as u32;
+    let min_y = y.clamp(0, img.height() as i64) as u32;
+    let max_x = (x + width as i64).clamp(0, img.width() as i64) as u32;
+    let max_y = (y + height as i64).clamp(0, img.height() as i64) as u32;
+
+    if max_x <= min_x || max_y <= min_y {
+        return None;
+    }
+
+    return Some(img.crop_imm(min_x, min_y, max_x - min_x, max_y - min_y));
+}
+
+
+
+pub fn linear_to_srgb(lin_rgb: [f32; 3]) -> [u8; 3] {
+    let mut srgb: [u8; 3] = [0; 3];
+
+    for i in 0..3 {
+        let v = lin_rgb[i];
+        if v <= 0.0 {
+            srgb[i] = 0 as u8;
+        } else if v >= 1.0 {
+            srgb[i] = 255 as u8;
+        } else {
+            srgb[i] = (v.powf(1.0 / 2.2) * 255.0 + 0.5) as u8; // Corrected formula
+        }
+    }
+
+    return srgb;
+}
+
+// Inverse of linear_to_srgb
+pub fn srgb_to_linear(srgb: [u8; 3]) -> [f32; 3] {
+    return srgb.map(|v| (v as f32 / 255.).powf(2.2));
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ColorFormat {
+    Hex,
+    Rgb,
+    Hsl,
+}
+
+pub fn format_color(color: [u8; 3], format: ColorFormat) -> String {
+    match format {
+        ColorFormat::Hex => format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2]),
+        ColorFormat::Rgb => format!("rgb({}, {}, {})", color[0], color[1], color[2]),
+        ColorFormat::Hsl => {
+            let [r, g, b] = color.map(|v| v as f32 / 255.);
+            let max = r.max(g).max(b);
+            let min = r.min(g).min(b);
+            let lightness = (max + min) / 2.;
+            let delta = max - min;
+
+            let (hue, saturation) = if delta == 0. {
+                (0., 0.)
+            }
+            else {
+                let saturation = delta / (1. - (2. * lightness - 1.).abs());
+                let hue = if max == r {
+                    60. * ((g - b) / delta).rem_euclid(6.)
+                } else if max == g {
+                    60. * ((b - r) / delta + 2.)
+                } else {
+                    60. * ((r - g) / delta + 4.)
+                };
+                (hue, saturation)
+            };
+
+            format!("hsl({}, {}%, {}%)", hue.round() as u32 % 360, (saturation * 100.).round(), (lightness * 100.).round())
+        }
+    }
+}
+
+// Where the image is shown on screen: the screen position of its top left corner and the size of
+// one of its pixels in points
+#[derive(PartialEq, Clone, Copy)]
+pub struct ViewTransform {
+    pub origin: Pos2,
+    pub scale: f32,
+}
+
+impl ViewTransform {
+    // The image stretched over rect
+    pub fn from_rect(rect: egui::Rect, real_image_size: [usize; 2]) -> Self {
+        return ViewTransform {
+            origin: rect.min,
+            scale: rect.width() / real_image_size[0] as f32
+        };
+    }
+
+    // The image with its center at center
+    pub fn centered(center: Pos2, scale: f32, real_image_size: [usize; 2]) -> Self {
+        return ViewTransform {
+            origin: center - Vec2::new(real_image_size[0] as f32, real_image_size[1] as f32) * scale / 2.,
+            scale: scale
+        };
+    }
+
+    pub fn to_screen(&self, pos: Pos2) -> Pos2 {
+        return self.origin + pos.to_vec2() * self.scale;
+    }
+
+    pub fn get_image_rect(&self, real_image_size: [usize; 2]) -> egui::Rect {
+        return egui::Rect::from_min_size(self.origin, Vec2::new(real_image_size[0] as f32, real_image_size[1] as f32) * self.scale);
+    }
+
+    // Changes the scale keeping the image pixel under the screen position fixed
+    pub fn zoom_around(&self, pos: Pos2, scale: f32) -> Self {
+        return ViewTransform {
+            origin: pos - (pos - self.origin) * (scale / self.scale),
+            scale: scale
+        };
+    }
+}
+
+// Screen position to image pixels
+pub fn get_real_image_pos(pos: Pos2, view: &ViewTransform) -> Pos2 {
+    return ((pos - view.origin) / view.scale).to_pos2();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_colors() {
+        assert_eq!(format_color([255, 128, 0], ColorFormat::Hex), "#ff8000");
+        assert_eq!(format_color([255, 128, 0], ColorFormat::Rgb), "rgb(255, 128, 0)");
+
+        assert_eq!(format_color([255, 0, 0], ColorFormat::Hsl), "hsl(0, 100%, 50%)");
+        assert_eq!(format_color([0, 128, 255], ColorFormat::Hsl), "hsl(210, 100%, 50%)");
+        assert_eq!(format_color([255, 0, 128], ColorFormat::Hsl), "hsl(330, 100%, 50%)");
+        assert_eq!(format_color([128, 128, 128], ColorFormat::Hsl), "hsl(0, 0%, 50%)");
+        assert_eq!(format_color([255, 255, 255], ColorFormat::Hsl), "hsl(0, 0%, 100%)");
+    }
+
+    fn rect(min: (f32, f32), max: (f32, f32)) -> egui::Rect {
+        return egui::Rect::from_min_max(Pos2::new(min.0, min.1), Pos2::new(max.0, max.1));
+    }
+
+    #[test]
+    fn resizes_the_crop_rectangle() {
+        let image_size = Vec2::new(1920., 1080.);
+        let crop_rect = rect((100., 100.), (200., 200.));
+
+        assert_eq!(resize_crop_rect(crop_rect, (1, 1), Pos2::new(500., 400.), None, image_size), rect((100., 100.), (500., 400.)));
+        // Dragging past the opposite corner flips the rectangle
+        assert_eq!(resize_crop_rect(crop_rect, (1, 1), Pos2::new(50., 60.), None, image_size), rect((50., 60.), (100., 100.)));
+        // A side handle only moves its side
+        assert_eq!(resize_crop_rect(crop_rect, (-1, 0), Pos2::new(20., 500.), None, image_size), rect((20., 100.), (200., 200.)));
+        // Kept inside the image
+        assert_eq!(resize_crop_rect(crop_rect, (1, 1), Pos2::new(5000., 5000.), None, image_size), rect((100., 100.), (1920., 1080.)));
+    }
+
+    #[test]
+    fn resizes_the_crop_rectangle_to_the_aspect_ratio() {
+        let image_size = Vec2::new(1920., 1080.);
+        let crop_rect = rect((100., 100.), (200., 200.));
+
+        // A corner follows the side that moved the most
+        assert_eq!(resize_crop_rect(crop_rect, (1, 1), Pos2::new(400., 200.), Some(1.), image_size), rect((100., 100.), (400., 400.)));
+        // A side handle grows the other axis around the center
+        assert_eq!(resize_crop_rect(crop_rect, (1, 0), Pos2::new(300., 150.), Some(1.), image_size), rect((100., 50.), (300., 250.)));
+        // Shrunk to fit between the anchor and the image border
+        assert_eq!(resize_crop_rect(crop_rect, (1, 1), Pos2::new(1900., 1000.), Some(1.), image_size), rect((100., 100.), (1080., 1080.)));
+    }
+
+    #[test]
+    fn fits_the_crop_rectangle_in_the_image() {
+        let image_size = Vec2::new(1920., 1080.);
+
+        assert_eq!(fit_crop_rect(rect((100., 100.), (500., 400.)), None, image_size), rect((100., 100.), (500., 400.)));
+        assert_eq!(fit_crop_rect(rect((100., 100.), (500., 400.)), Some(1.), image_size), rect((100., 100.), (500., 500.)));
+        // Moved back inside, then shrunk when it is larger than the image
+        assert_eq!(fit_crop_rect(rect((1800., 0.), (2200., 300.)), None, image_size), rect((1520., 0.), (1920., 300.)));
+        assert_eq!(fit_crop_rect(rect((0., 0.), (3840., 1080.)), None, image_size), rect((0., 0.), (1920., 540.)));
+
+        // Sizes that come out a rounding error larger than the image once scaled
+        for (width, height) in [(1920., 1080.), (2560., 1440.), (1366., 768.), (3840., 2160.)] {
+            let image_size = Vec2::new(width, height);
+            for aspect in [AspectRatio::Free, AspectRatio::Square, AspectRatio::Wide, AspectRatio::Standard] {
+                for crop_width in (1..6000).step_by(7) {
+                    let fitted = fit_crop_rect(rect((10., 10.), (10. + crop_width as f32, 500.)), aspect.value(), image_size);
+                    assert!(fitted.min.x >= 0. && fitted.min.y >= 0. && fitted.max.x <= width && fitted.max.y <= height);
+                }
+            }
+        }
+        let fitted = fit_crop_rect(rect((0., 0.), (2154., 1080.)), AspectRatio::Wide.value(), image_size);
+        assert!(fitted.min.x >= 0. && fitted.min.y >= 0. && fitted.max.x <= image_size.x && fitted.max.y <= image_size.y);
+    }
+
+    #[test]
+    fn keeps_the_resize_aspect_ratio() {
+        let mut dialog = ResizeDialog::new(1920, 1080);
+        dialog.width = 960;
+        dialog.update_height();
+        assert_eq!((dialog.width, dialog.height), (960, 540));
+        assert_eq!(dialog.get_percentage(), 50.);
+
+        dialog.height = 1;
+        dialog.update_width();
+        assert_eq!((dialog.width, dialog.height), (2, 1));
+
+        dialog.lock_aspect = false;
+        dialog.width = 100;
+        dialog.update_height();
+        assert_eq!((dialog.width, dialog.height), (100, 1));
+    }
+
+    #[test]
+    fn resizes_by_percentage() {
+        let mut dialog = ResizeDialog::new(1920, 1080);
+        dialog.set_percentage(50.);
+        assert_eq!((dialog.width, dialog.height), (960, 540));
+        dialog.set_percentage(0.01);
+        assert_eq!((dialog.width, dialog.height), (1, 1));
+
+        // Lowered so the larger side stays within MAX_IMAGE_SIZE
+        let mut dialog = ResizeDialog::new(4000, 2000);
+        dialog.set_percentage(800.);
+        assert_eq!((dialog.width, dialog.height), (MAX_IMAGE_SIZE, MAX_IMAGE_SIZE / 2));
+
+        let mut dialog = ResizeDialog::new(100, 10000);
+        dialog.width = 1000;
+        dialog.update_height();
+        assert_eq!(dialog.height, MAX_IMAGE_SIZE);
+    }
+
+    #[test]
+    fn transforms_points_like_the_image() {
+        let mut image = image::RgbaImage::new(4, 3);
+        image.put_pixel(1, 0, Rgba([255, 0, 0, 255]));
+        let image = DynamicImage::ImageRgba8(image);
+
+        for transform in [Transform::RotateLeft, Transform::RotateRight, Transform::Rotate180, Transform::FlipHorizontal, Transform::FlipVertical] {
+            let transformed = transform.apply_to_image(&image);
+            // The center of the marked pixel lands in the center of the same pixel after the transform
+            let (x, y) = transform.apply_to_point((1.5, 0.5), (4., 3.));
+            assert_eq!(transformed.get_pixel(x as u32, y as u32).0, [255, 0, 0, 255]);
+            assert_eq!((x.fract(), y.fract()), (0.5, 0.5));
+        }
+
+        assert_eq!(Transform::RotateRight.apply_to_point((0., 0.), (4., 3.)), (3., 0.));
+        assert_eq!(Transform::RotateLeft.apply_to_point((0., 0.), (4., 3.)), (0., 4.));
+        // Undone by the opposite rotation, the size being swapped in between
+        let rotated = Transform::RotateRight.apply_to_point((1., 2.), (4., 3.));
+        assert_eq!(Transform::RotateLeft.apply_to_point(rotated, (3., 4.)), (1., 2.));
+    }
+
+
+    fn assert_near(a: (f32, f32), b: (f32, f32)) {
+        assert!((a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3, "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn caps_the_arrow_head_length() {
+        // A 2 pixel line gets a 12 pixel long head, 7.2 pixels to each side
+        let geometry = get_arrow_geometry((0., 0.), (100., 0.), 2., ArrowHeads::Single, ArrowStyle::Open);
+        assert_eq!(geometry.heads.len(), 1);
+        assert_near(geometry.heads[0][0], (88., 7.2));
+        assert_near(geometry.heads[0][1], (100., 0.));
+        assert_near(geometry.heads[0][2], (88., -7.2));
+
+        // No longer than the arrow, or than half of it with two heads
+        let geometry = get_arrow_geometry((0., 0.), (0., 10.), 2., ArrowHeads::Single, ArrowStyle::Open);
+        assert_near(geometry.heads[0][0], (-6., 0.));
+        let geometry = get_arrow_geometry((0., 0.), (0., 10.), 2., ArrowHeads::Double, ArrowStyle::Open);
+        assert_near(geometry.heads[0][0], (-3., 5.));
+        assert_near(geometry.heads[1][2], (3., 5.));
+    }
+
+    #[test]
+    fn adds_a_head_at_each_end_of_double_arrows() {
+        let geometry = get_arrow_geometry((10., 20.), (110., 20.), 2., ArrowHeads::Double, ArrowStyle::Open);
+        assert_eq!(geometry.heads.len(), 2);
+        assert_near(geometry.heads[0][1], (110., 20.));
+        assert_near(geometry.heads[1][1], (10., 20.));
+        assert_near(geometry.heads[1][2], (22., 12.8));
+        // The open style keeps the whole shaft
+        assert_eq!(geometry.shaft, ((10., 20.), (110., 20.)));
+
+        assert!(get_arrow_geometry((10., 20.), (10., 20.), 2., ArrowHeads::Double, ArrowStyle::Open).heads.is_empty());
+    }
+
+    #[test]
+    fn stops_the_filled_arrow_shaft_before_the_tip() {
+        let geometry = get_arrow_geometry((0., 0.), (100., 0.), 2., ArrowHeads::Single, ArrowStyle::Filled);
+        // Inside the head, between its base at 88 and the tip
+        assert_near(geometry.shaft.0, (0., 0.));
+        assert_near(geometry.shaft.1, (90.4, 0.));
+
+        let geometry = get_arrow_geometry((0., 0.), (100., 0.), 2., ArrowHeads::Double, ArrowStyle::Filled);
+        assert_near(geometry.shaft.0, (9.6, 0.));
+        assert_near(geometry.shaft.1, (90.4, 0.));
+    }
+
+    #[test]
+    fn snaps_the_angle_to_15_degrees() {
+        let start = Pos2::new(10., 10.);
+
+        let snapped = snap_angle(start, Pos2::new(110., 15.), 15.);
+        assert_near((snapped.x, snapped.y), (10. + (100f32.powi(2) + 25.).sqrt(), 10.));
+
+        // 40 degrees goes to 45, keeping the length
+        let end = start + Vec2::angled(40f32.to_radians()) * 50.;
+        let snapped = snap_angle(start, end, 15.);
+        assert_near((snapped.x, snapped.y), (10. + 50. * 45f32.to_radians().cos(), 10. + 50. * 45f32.to_radians().sin()));
+
+        // Straight up stays straight up
+        let snapped = snap_angle(start, Pos2::new(10., -40.), 15.);
+        assert_near((snapped.x, snapped.y), (10., -40.));
+    }
+
+
+    // Draws a stroke through the points on a white image
+    fn draw_stroke(size: (u32, u32), points: &[(f32, f32)], color: [u8; 4], width: f32) -> DynamicImage {
+        let mut img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(size.0, size.1, Rgba([255, 255, 255, 255])));
+        let mut stroke = BrushStroke::new(&mut img, points[0], color, width);
+        for point in &points[1..] {
+            stroke.add_point(&mut img, *point);
+        }
+        return img;
+    }
+
+    #[test]
+    fn blends_a_translucent_stroke_once_where_it_crosses_itself() {
+        let color = [255, 0, 0, 128];
+        // Right, down, left and back up through the first segment at (20.5, 20.5)
+        let img = draw_stroke((40, 40), &[(5.5, 20.5), (35.5, 20.5), (35.5, 35.5), (20.5, 35.5), (20.5, 5.5)], color, 6.);
+
+        let mut single = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, 255])));
+        blend_pixel(&mut single, 0, 0, color, 1.);
+        assert_eq!(single.get_pixel(0, 0).0, [255, 127, 127, 255]);
+
+        assert_eq!(img.get_pixel(20, 20).0, single.get_pixel(0, 0).0);
+        assert_eq!(img.get_pixel(10, 20).0, single.get_pixel(0, 0).0);
+        // The corners are joined without a darker spot either
+        assert_eq!(img.get_pixel(35, 20).0, single.get_pixel(0, 0).0);
+    }
+
+    #[test]
+    fn shows_the_stroke_image_like_the_exported_stroke() {
+        let color = [0, 200, 50, 100];
+        let points = [(8.3, 12.1), (30.7, 14.2), (12.5, 30.9), (14.1, 6.6)];
+        let exported = draw_stroke((40, 40), &points, color, 7.);
+
+        let ((offset_x, offset_y), stroke_image) = get_stroke_image(&points, color, 7.).unwrap();
+        let mut shown = DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 40, Rgba([255, 255, 255, 255])));
+        for (x, y, pixel) in stroke_image.enumerate_pixels() {
+            let [red, green, blue, alpha] = pixel.0;
+            blend_pixel(&mut shown, offset_x + x as i32, offset_y + y as i32, [red, green, blue, 255], alpha as f32 / 255.);
+        }
+
+        for (x, y, pixel) in exported.pixels() {
+            let shown_pixel = shown.get_pixel(x, y).0;
+            for i in 0..4 {
+                assert!((pixel.0[i] as i32 - shown_pixel[i] as i32).abs() <= 1, "({}, {}): {:?} {:?}", x, y, pixel.0, shown_pixel);
+            }
+        }
+        assert!(get_stroke_image(&[], color, 7.).is_none());
+    }
+
+
+    // Black and white squares of 3 pixels, like small text
+    fn get_checkerboard(width: u32, height: u32) -> DynamicImage {
+        return DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            if (x / 3 + y / 3) % 2 == 0 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) }
+        }));
+    }
+
+    #[test]
+    fn redacts_every_pixel_of_the_area() {
+        let original = get_checkerboard(80, 60);
+
+        for style in [RedactStyle::Blur, RedactStyle::Pixelate, RedactStyle::Fill] {
+            // Even the weakest strength replaces every pixel, and the 43 pixels wide area does not
+            // end with a thin column of blocks
+            let mut img = original.clone();
+            redact_image(&mut img, 10, 5, 43, 30, style, 1, [255, 0, 0, 255]);
+
+            for (x, y, pixel) in img.pixels() {
+                let inside = (10..53).contains(&x) && (5..35).contains(&y);
+                assert_eq!(pixel != original.get_pixel(x, y), inside, "{:?} at ({}, {})", style, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn raises_the_pixelate_block_size() {
+        let mut img = get_checkerboard(40, 40);
+        redact_image(&mut img, 0, 0, 40, 40, RedactStyle::Pixelate, 2, [0, 0, 0, 255]);
+        // 10 pixel blocks for a 40 pixel area, above MIN_REDACT_BLOCK
+        assert_eq!(img.get_pixel(0, 0), img.get_pixel(9, 9));
+        assert_ne!(img.get_pixel(9, 9), img.get_pixel(10, 10));
+
+        let mut img = get_checkerboard(20, 20);
+        redact_image(&mut img, 0, 0, 20, 20, RedactStyle::Pixelate, 2, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(0, 0), img.get_pixel(MIN_REDACT_BLOCK - 1, MIN_REDACT_BLOCK - 1));
+    }
+
+    #[test]
+    fn keeps_redacted_pixels_under_the_eraser() {
+        let original = get_checkerboard(40, 40);
+        let mut document = crate::document::Document::new(original.clone());
+        document.redact(10, 10, 20, 20, RedactStyle::Pixelate, MIN_REDACT_BLOCK, [0, 0, 0, 255]);
+        document.add(crate::annotation::Annotation::Erase { points: vec![(5., 5.), (35., 35.)], width: 60. });
+
+        let flattened = document.flatten();
+        for y in 10..30 {
+            for x in 10..30 {
+                assert_eq!(flattened.get_pixel(x, y), document.background().get_pixel(x, y));
+                assert_ne!(flattened.get_pixel(x, y), original.get_pixel(x, y));
+            }
+        }
+    }
+
+
+    #[test]
+    fn covers_the_stroke_edge_partially() {
+        // 4.5 pixels wide along the middle of row 20
+        let img = draw_stroke((40, 40), &[(10.5, 20.5), (30.5, 20.5)], [0, 0, 0, 255], 4.5).to_rgba8();
+
+        for y in 19..=21 {
+            assert_eq!(img.get_pixel(20, y), &Rgba([0, 0, 0, 255]));
+        }
+        for y in [18, 22] {
+            let value = img.get_pixel(20, y).0[0];
+            assert!(value > 0 && value < 255, "{}", value);
+        }
+        assert_eq!(img.get_pixel(20, 17), &Rgba([255, 255, 255, 255]));
+        assert_eq!(img.get_pixel(20, 23), &Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn draws_a_round_dot_for_a_single_point() {
+        let img = draw_stroke((40, 40), &[(20.5, 20.5)], [0, 0, 0, 255], 9.).to_rgba8();
+
+        for (x, y) in [(20, 20), (16, 20), (24, 20), (20, 16), (20, 24), (22, 22), (18, 18)] {
+            assert_eq!(img.get_pixel(x, y), &Rgba([0, 0, 0, 255]), "{} {}", x, y);
+        }
+        // The diagonal is rounded off
+        let value = img.get_pixel(23, 23).0[0];
+        assert!(value > 0 && value < 255, "{}", value);
+        for (x, y) in [(16, 16), (24, 16), (16, 24), (24, 24)] {
+            assert_eq!(img.get_pixel(x, y), &Rgba([255, 255, 255, 255]), "{} {}", x, y);
+        }
+    }
+
+    #[test]
+    fn smooths_the_points_but_keeps_the_last_one() {
+        let points = [(0., 0.), (10., 0.), (10., 10.)];
+        assert_eq!(smooth_points(&points, 0.), points.to_vec());
+
+        let smoothed = smooth_points(&points, 0.5);
+        assert_eq!(smoothed, vec![(0., 0.), (5., 0.), (7.5, 5.), (10., 10.)]);
+        assert_eq!(smooth_points(&[(1., 2.)], 0.5), vec![(1., 2.)]);
+        assert!(smooth_points(&[], 0.5).is_empty());
+    }
+
+
+    #[test]
+    fn zooms_around_the_pointer() {
+        let view = ViewTransform { origin: Pos2::new(30., -20.), scale: 1.5 };
+        let pos = Pos2::new(200., 150.);
+        let image_pos = get_real_image_pos(pos, &view);
+
+        for scale in [0.25, 1., 3., 16.] {
+            let zoomed = view.zoom_around(pos, scale);
+            assert_eq!(zoomed.scale, scale);
+            assert_near(get_real_image_pos(pos, &zoomed).into(), image_pos.into());
+            assert_near(zoomed.to_screen(image_pos).into(), pos.into());
+        }
+    }
+
+    #[test]
+    fn maps_between_the_screen_and_the_image() {
+        let view = ViewTransform::centered(Pos2::new(400., 300.), 2., [200, 100]);
+        assert_eq!(view.origin, Pos2::new(200., 200.));
+        assert_eq!(view.get_image_rect([200, 100]), egui::Rect::from_min_max(Pos2::new(200., 200.), Pos2::new(600., 400.)));
+        assert!(ViewTransform::from_rect(view.get_image_rect([200, 100]), [200, 100]) == view);
+
+        for image_pos in [Pos2::new(0., 0.), Pos2::new(100., 50.), Pos2::new(199.5, 12.25), Pos2::new(-10., 300.)] {
+            let screen_pos = view.to_screen(image_pos);
+            assert_near(get_real_image_pos(screen_pos, &view).into(), image_pos.into());
+        }
+        assert_eq!(view.to_screen(Pos2::new(100., 50.)), Pos2::new(400., 300.));
+    }
+}